@@ -8,6 +8,8 @@ pub enum CommErr {
     ReqErr(#[from] reqwest::Error),
      #[error("Remote API return error: 【{0}】.")]
     HttpResErr(&'static str),
+     #[error("File download error: 【{0}】.")]
+    DownloadErr(String),
      #[error("Serialize error: 【{0}】.")]
     JsonErr(#[from] serde_json::Error),
      #[error("Module default error: 【{0}】.")]
@@ -18,6 +20,10 @@ pub enum CommErr {
     GetValueStrErr(&'static str),
      #[error("[Config setting [{0}] do not exist")]
     ConfigErr(#[from] std::env::VarError),
+     #[error("Database property [{0}] do not exist.")]
+    PropertyErr(String),
+     #[error("Invalid Notion ID or URL: 【{0}】.")]
+    IdErr(String),
      #[error("Unsupport Notion Paragraph Format to Reading for now!")]
     UnsupportErr,
 }
\ No newline at end of file
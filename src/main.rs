@@ -1,4 +1,5 @@
-use notion_api::notion::{Notion, property::PropertyType, sort::Direction, database::Database};
+use notion_api::notion::{Notion, property::PropertyType, sort::Direction, database::Database, block::{BlockElement, BlockReader}, asset::Assets, render::Markdown, front_matter::{FrontMatter, MatterFormat}};
+use std::{fs::File, io::{BufWriter, Write}};
 use anyhow::{Result, Ok};
 use dotenv::dotenv;
 use std::env;
@@ -10,14 +11,30 @@ fn main() -> Result<()> {
     let s2 = PropertyType::MultiSelect("Tag").contains("test");
     let filter = s1.and(s2);
 
-    let mut database = Notion::Databases(env::var("DB_ID")?)
+    let mut database = Notion::Databases(env::var("DB_ID")?.parse()?)
         .filter(filter)
         .sort(PropertyType::Date("Edited time"), Direction::Descending)
         .search::<Database>()?;
 
+    let dir = env!("CARGO_MANIFEST_DIR").to_string();
+    let links = database.links("md");
+    let mut assets = Assets::new(dir.to_string() + "/assets", "assets");
+    let matter = FrontMatter::new(MatterFormat::Yaml);
     for page in database.page_list.iter_mut() {
-        let path = env!("CARGO_MANIFEST_DIR").to_string() + "/" + &page.title + ".md";
-        std::fs::write(path, page.content()?)?;
+        let path = dir.to_string() + "/" + &page.title + ".md";
+        assets.localize(page)?;
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(page.front_matter(&matter).as_bytes())?;
+        page.write_with(&Markdown::default(), &mut BlockReader::default(), &mut file, |list| {
+            for warning in BlockElement::warnings(list) {
+                eprintln!("{}: {}", page.title, warning);
+            }
+            for be in list.iter_mut() {
+                be.relink(&links);
+            }
+            assets.localize_blocks(list)
+        })?;
+        file.flush()?;
     }
 
     Ok(())
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use super::{Notion, id::BlockId, get_property_value, block::{BlockElement, Media}, page::{Page, file_url}, request::Request};
+
+
+/**
+ * 导出页面时下载页面引用的文件到本地目录，并将链接改写为相对路径
+ * 文件以内容的SHA-256命名，相同内容只保存一份
+ */
+#[derive(Debug)]
+pub struct Assets {
+    dir: PathBuf,
+    prefix: String,
+    external: bool,
+    // 已下载的链接 => 相对路径
+    files: HashMap<String, String>,
+}
+
+impl Assets {
+    /**
+     * dir为资源的保存目录，prefix为导出文档引用该目录时使用的相对路径
+     */
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str) -> Self {
+        Assets {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.trim_end_matches('/').to_string(),
+            external: false,
+            files: HashMap::new(),
+        }
+    }
+
+    /**
+     * 是否同时下载外部链接的文件，默认只下载Notion托管的文件
+     */
+    pub fn external(mut self, external: bool) -> Self {
+        self.external = external;
+        self
+    }
+
+    /**
+     * 下载页面内容、图标和封面引用的文件，并改写为本地链接
+     * 流式写出页面时内容尚未获取，只处理图标和封面，内容由localize_blocks逐页处理
+     */
+    pub fn localize(&mut self, page: &mut Page) -> Result<()> {
+        self.localize_blocks(&mut page.content.inner)?;
+
+        let id = page.id.clone();
+        let hosted = page.hosted.clone();
+        for (index, url) in [("icon", &mut page.icon), ("cover", &mut page.cover)] {
+            if !url.contains("://") || !(self.external || hosted.contains(&index)) {
+                continue;
+            }
+
+            // 页面对象中的链接同样会过期，下载失败时重新获取页面再试一次
+            *url = match self.download(url, "") {
+                Ok(path) => path,
+                Err(_) => self.download(&file_url(&Notion::Pages(id.clone()).retrieve()?, index), "")?,
+            };
+        }
+
+        Ok(())
+    }
+
+    pub fn localize_blocks(&mut self, list: &mut [BlockElement]) -> Result<()> {
+        for be in list.iter_mut() {
+            if let (true, Some(media)) = (be.line_type.is_media(), be.media.as_mut()) {
+                if media.hosted || self.external {
+                    // 托管文件的链接已过期或下载失败时，重新获取块以得到新的链接
+                    let source = if media.hosted { be.id.clone() } else { None };
+                    if let (Some(id), true) = (&source, is_expired(media)) {
+                        *media = refetch(id)?;
+                    }
+                    media.url = match (self.download(&media.url, &media.name), &source) {
+                        (Ok(path), _) => path,
+                        (Err(_), Some(id)) => {
+                            *media = refetch(id)?;
+                            self.download(&media.url, &media.name)?
+                        },
+                        (Err(err), None) => return Err(err),
+                    };
+                    media.hosted = false;
+                    media.expiry_time = None;
+                }
+            }
+
+            self.localize_blocks(&mut be.child)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * 下载单个文件，返回导出文档中使用的相对路径
+     */
+    pub fn download(&mut self, url: &str, name: &str) -> Result<String> {
+        if let Some(path) = self.files.get(url) {
+            return Ok(path.to_string());
+        }
+
+        let data = Request::download(url)?;
+        let digest = format!("{:x}", Sha256::digest(&data));
+
+        let file = match extension(name).or_else(|| extension(url.split(['?', '#']).next().unwrap_or_default())) {
+            Some(ext) => format!("{}.{}", digest, ext),
+            None => digest,
+        };
+
+        let target = self.dir.join(&file);
+        if !target.exists() {
+            std::fs::create_dir_all(&self.dir)?;
+            std::fs::write(target, data)?;
+        }
+
+        let path = if self.prefix.is_empty() { file } else { self.prefix.to_string() + "/" + &file };
+        self.files.insert(url.to_string(), path.to_string());
+        Ok(path)
+    }
+}
+
+/**
+ * 重新获取块，得到新的文件链接
+ */
+fn refetch(id: &BlockId) -> Result<Media> {
+    Media::new(get_property_value(&Notion::Blocks(id.clone()).retrieve()?, None)?)
+}
+
+/**
+ * 文件扩展名，只保留较短的字母数字组合
+ */
+fn extension(name: &str) -> Option<String> {
+    let ext = Path::new(name.rsplit('/').next().unwrap_or_default()).extension()?.to_str()?.to_lowercase();
+    (!ext.is_empty() && ext.len() <= 8 && ext.chars().all(|c| c.is_ascii_alphanumeric())).then_some(ext)
+}
+
+/**
+ * 链接的过期时间与当前UTC时间按字符串比较，格式均为2023-05-01T09:30:00
+ */
+fn is_expired(media: &Media) -> bool {
+    match media.expiry_time.as_ref().and_then(|time| time.get(..19)) {
+        Some(time) => time <= now().as_str(),
+        None => false,
+    }
+}
+
+fn now() -> String {
+    timestamp(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default())
+}
+
+/**
+ * Unix时间戳（秒）转为UTC时间
+ */
+fn timestamp(secs: u64) -> String {
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // 由天数换算公历日期
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_to_utc() {
+        assert_eq!(timestamp(0), "1970-01-01T00:00:00");
+        assert_eq!(timestamp(951_782_400), "2000-02-29T00:00:00");
+        assert_eq!(timestamp(1_682_933_400), "2023-05-01T09:30:00");
+        assert_eq!(timestamp(4_102_444_799), "2099-12-31T23:59:59");
+    }
+
+    #[test]
+    fn now_is_comparable() {
+        let now = now();
+        assert_eq!(now.len(), 19);
+        assert!(now.as_str() > "2023-01-01T00:00:00");
+    }
+
+    #[test]
+    fn expiry() {
+        let media = |time: &str| Media { expiry_time: Some(time.to_string()), ..Media::default() };
+        assert!(is_expired(&media("2000-01-01T00:00:00.000Z")));
+        assert!(!is_expired(&media("9999-01-01T00:00:00.000Z")));
+        assert!(!is_expired(&Media::default()));
+    }
+
+    #[test]
+    fn file_extension() {
+        assert_eq!(extension("photo.JPG"), Some("jpg".to_string()));
+        assert_eq!(extension("https://s3.example.com/a/b/report.pdf"), Some("pdf".to_string()));
+        assert_eq!(extension("archive.tar.gz"), Some("gz".to_string()));
+        assert_eq!(extension("no_extension"), None);
+        assert_eq!(extension("weird.ext-with-dash"), None);
+    }
+}
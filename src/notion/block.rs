@@ -1,17 +1,140 @@
 use std::str::FromStr;
-use std::fmt::Display as FmtDisplay;
-use anyhow::Result;
+use std::collections::{HashMap, HashSet};
 use strum::EnumProperty;
-use serde_json::Map;
+use std::fmt::{self, Display as FmtDisplay};
+use std::io;
+use anyhow::Result;
+use serde_json::{Map, json};
 
-use super::{Notion, CommErr, get_value_str, get_property_value, Json, NewImp, text::*};
+use super::{Notion, NotionBuilder, CommErr, APPEND_LIMIT, id::{PageId, BlockId}, get_value_str, get_property_value, Json, NewImp, text::*, import, database::{self, TableOptions}, model::BlockObject, render::{self, Renderer, Visitor, Markdown}};
 
 
-#[derive(Debug)]
+// 单个rich text对象的最大字符数
+const RICH_TEXT_LIMIT: usize = 2000;
+
+
+/**
+ * 块的解析模式
+ * Lenient：未知或不支持的块保留原始Json，作为Unsupported块继续解析
+ * Strict：遇到此类块时返回UnsupportErr
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ParseMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+/**
+ * 获取块及其全部子块，子块按next_cursor分页获取
+ * 展开同步块时记录来源，同步块互相引用时不再重复展开
+ */
+#[derive(Debug, Default, Clone)]
+pub struct BlockReader {
+    mode: ParseMode,
+    syncing: Vec<BlockId>,
+}
+
+impl BlockReader {
+    /**
+     * mode为遇到无法解析的块时的处理方式
+     */
+    pub fn new(mode: ParseMode) -> Self {
+        BlockReader { mode, syncing: Vec::new() }
+    }
+
+    /**
+     * 页面或块的全部子块
+     */
+    pub fn read(&mut self, id: BlockId) -> Result<Block> {
+        let mut inner = Vec::new();
+        for value in children(id)?.iter() {
+            inner.push(self.element(value)?);
+        }
+
+        Ok(Block { inner })
+    }
+
+    /**
+     * 逐页获取页面或块的子块，每获取一页（最多100个块及其子块）就交给f处理
+     */
+    pub fn pages(&mut self, id: BlockId, mut f: impl FnMut(Vec<BlockElement>) -> Result<()>) -> Result<()> {
+        NotionBuilder::new(Notion::Blocks(id)).pages(None, |page| {
+            let list = page.iter().map(|value| self.element(value)).collect::<Result<Vec<BlockElement>>>()?;
+            f(list)
+        })
+    }
+
+    /**
+     * 页面或块的全部子块对象，保留块的元数据和全部字段
+     */
+    pub fn objects(&mut self, id: BlockId) -> Result<Vec<BlockObject>> {
+        children(id)?.iter().map(|value| self.object(value)).collect()
+    }
+
+    /**
+     * 解析单个块并获取其子块
+     */
+    pub fn element(&mut self, value: &Json) -> Result<BlockElement> {
+        BlockElement::parse(value, self.mode, || {
+            // TODO: 异步
+            let block = get_property_value(value, None)?;
+            let has_children = value.get("has_children")
+                .ok_or(CommErr::FormatErr("has_children"))?
+                .as_bool().ok_or(CommErr::FormatErr("has_children"))?;
+            let synced_from = block.get("synced_from").and_then(|from| from.get("block_id")).and_then(|id| id.as_str());
+            match (get_value_str(value, "type")?.as_str(), synced_from) {
+                ("synced_block", Some(source)) => Ok(self.synced(source.parse()?, BlockReader::read)?.inner),
+                _ if has_children => Ok(self.read(get_value_str(value, "id")?.parse()?)?.inner),
+                _ => Ok(Vec::new()),
+            }
+        })
+    }
+
+    /**
+     * 解析单个块对象并获取其子块对象
+     */
+    pub fn object(&mut self, value: &Json) -> Result<BlockObject> {
+        let mut object: BlockObject = serde_json::from_value(value.to_owned())?;
+        object.children = match object.synced_from().cloned() {
+            Some(source) => self.synced(source, BlockReader::objects)?,
+            None if object.has_children && !object.is_page() => self.objects(object.id.clone())?,
+            None => Vec::new(),
+        };
+
+        Ok(object)
+    }
+
+    /**
+     * 展开同步块的来源，来源已在展开中（同步块互相引用）时返回空内容
+     */
+    fn synced<T: Default>(&mut self, source: BlockId, fetch: impl FnOnce(&mut Self, BlockId) -> Result<T>) -> Result<T> {
+        if self.syncing.contains(&source) {
+            return Ok(T::default());
+        }
+
+        self.syncing.push(source.clone());
+        let res = fetch(self, source);
+        self.syncing.pop();
+
+        res
+    }
+}
+
+/**
+ * 按next_cursor获取全部子块的Json
+ */
+fn children(id: BlockId) -> Result<Vec<Json>> {
+    NotionBuilder::new(Notion::Blocks(id)).results(None)
+}
+
+
+#[derive(Debug, Clone)]
 pub struct FragmentText {
     pub text: String,
     pub href: String,
     pub annotation: Vec<Annotation>,
+    pub inline: Inline,
 }
 
 impl FragmentText {
@@ -35,179 +158,722 @@ impl FragmentText {
         }
 
         Annotation::sort(&mut annotation);
-        Ok(FragmentText  {
-            text: get_value_str(val, "plain_text")?,
-            href: val.get("href").unwrap_or(&Json::default()).as_str().unwrap_or_default().to_string(),
-            annotation
-        })
+        let text = get_value_str(val, "plain_text")?;
+        let inline = FragmentText::inline(val, &text)?;
+        let mut href = val.get("href").unwrap_or(&Json::default()).as_str().unwrap_or_default().to_string();
+        if href.is_empty() {
+            match &inline {
+                Inline::Page(id) => href = id.url(),
+                Inline::Database(id) => href = id.url(),
+                _ => (),
+            }
+        }
+
+        Ok(FragmentText  { text, href, annotation, inline })
     }
-}
 
-impl FmtDisplay for FragmentText  {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut anno_format = "{}".to_string();
-        let mut conflict = false;
+    /**
+     * 解析mention和equation类型的rich text，无法识别的类型按普通文本处理
+     */
+    fn inline(val: &Json, text: &str) -> Result<Inline> {
+        let inline = match get_value_str(val, "type")?.as_str() {
+            "equation" => Inline::Equation(get_value_str(get_property_value(val, None)?, "expression")?),
+            "mention" => {
+                let mention = get_property_value(val, None)?;
+                let value = get_property_value(mention, None)?;
+                match get_value_str(mention, "type")?.as_str() {
+                    "user" => Inline::User {
+                        id: get_value_str(value, "id")?.parse()?,
+                        // 无权限读取用户信息时没有name
+                        name: get_value_str(value, "name").unwrap_or_else(|_| text.trim_start_matches('@').to_string()),
+                    },
+                    "page" => Inline::Page(get_value_str(value, "id")?.parse()?),
+                    "database" => Inline::Database(get_value_str(value, "id")?.parse()?),
+                    "date" => Inline::Date { start: get_value_str(value, "start")?, end: get_value_str(value, "end").ok() },
+                    "link_preview" => Inline::LinkPreview(get_value_str(value, "url")?),
+                    "template_mention" => Inline::TemplateMention(get_property_value(value, None)?.as_str().unwrap_or_default().to_string()),
+                    _ => Inline::Text,
+                }
+            },
+            _ => Inline::Text,
+        };
+
+        Ok(inline)
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        FragmentText::from_link(text, "")
+    }
+
+    pub fn from_link(text: &str, href: &str) -> Self {
+        FragmentText { text: text.to_string(), href: href.to_string(), annotation: Vec::new(), inline: Inline::Text }
+    }
+
+    /**
+     * 解析rich text数组
+     */
+    pub fn line(val: &Json) -> Result<Vec<FragmentText>> {
+        let mut line = Vec::new();
+        for v in val.as_array().ok_or(CommErr::FormatErr("rich text"))?.iter() {
+            line.push(FragmentText::new(v)?);
+        }
+
+        Ok(line)
+    }
+
+    /**
+     * 合并链接和样式都相同的相邻片段
+     */
+    pub fn merge(line: &[FragmentText]) -> Vec<FragmentText> {
+        let mut output: Vec<FragmentText> = Vec::new();
+        for fragment in line.iter() {
+            match output.last_mut() {
+                Some(last) if last.inline == Inline::Text && fragment.inline == Inline::Text
+                    && last.href == fragment.href && last.annotation == fragment.annotation => last.text.push_str(&fragment.text),
+                _ => output.push(fragment.clone()),
+            }
+        }
+
+        output
+    }
+
+    /**
+     * 生成Notion API所需的rich text数组，超出长度限制的文本会被拆分
+     */
+    pub fn to_json(&self) -> Vec<Json> {
+        let mut annotations = json!({
+            "bold": false, "italic": false, "strikethrough": false,
+            "underline": false, "code": false, "color": "default",
+        });
         for anno in self.annotation.iter() {
-            anno_format = match anno {
-                Annotation::Color(AnnoColor::Default) => anno_format,
-                Annotation::Color(c) => (&anno_format).replace("{}", anno.get_str("md").unwrap()).replace("{color}", c.get_str("md").unwrap()),
-                Annotation::Bold|Annotation::Italic => {
-                    let anno_prop = if !conflict { conflict = true; "md" } else { conflict=false;"mdrpl" };
-                    (&anno_format).replace("{}", anno.get_str(anno_prop).unwrap())
-                },
-                Annotation::Code => anno.get_str("md").unwrap().to_string(),
-                _ => (&anno_format).replace("{}", anno.get_str("md").unwrap()),
+            annotations[anno.to_string()] = match anno {
+                Annotation::Color(c) => json!(c.name()),
+                _ => json!(true),
             };
         }
 
-        write!(f, "{}", anno_format.replace("{}", &self.text))
+        // Notion只接受绝对链接
+        let link = if self.href.contains("://") || self.href.starts_with("mailto:") {
+            json!({ "url": self.href })
+        } else {
+            Json::Null
+        };
+
+        let mention = match &self.inline {
+            Inline::User { id, .. } => Some(json!({ "id": id })),
+            Inline::Page(id) => Some(json!({ "id": id })),
+            Inline::Database(id) => Some(json!({ "id": id })),
+            Inline::Date { start, end } => Some(json!({ "start": start, "end": end })),
+            Inline::Equation(expression) => {
+                return vec![json!({ "type": "equation", "equation": { "expression": expression }, "annotations": annotations })];
+            },
+            // 链接预览和模板提及无法通过API创建，按普通文本输出
+            _ => None,
+        };
+        if let Some(mention) = mention {
+            let mention_type = self.inline.to_string();
+            return vec![json!({
+                "type": "mention",
+                "mention": { "type": mention_type, mention_type.as_str(): mention },
+                "annotations": annotations,
+            })];
+        }
+
+        split_utf16(&self.text, RICH_TEXT_LIMIT).into_iter().map(|content| json!({
+            "type": "text",
+            "text": { "content": content, "link": link },
+            "annotations": annotations,
+        })).collect()
+    }
+}
+
+/**
+ * 按UTF-16编码单元拆分文本，Notion以此计算rich text的长度，代理对不会被拆开
+ */
+fn split_utf16(text: &str, limit: usize) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    let mut len = 0;
+    for c in text.chars() {
+        if chunks.is_empty() || len + c.len_utf16() > limit {
+            chunks.push(String::default());
+            len = 0;
+        }
+        len += c.len_utf16();
+        chunks.last_mut().unwrap().push(c);
+    }
+
+    chunks
+}
+
+impl FmtDisplay for FragmentText  {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Markdown::default().fragment(self))
+    }
+}
+
+
+/**
+ * 图片、视频、文件等块引用的资源，Notion托管的文件链接在expiry_time之后失效
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Media {
+    pub url: String,
+    pub hosted: bool,
+    pub expiry_time: Option<String>,
+    pub name: String,
+}
+
+impl Media {
+    pub fn new(block: &Json) -> Result<Self> {
+        let hosted = get_value_str(block, "type")? == "file";
+        let source = get_property_value(block, None)?;
+        let url = get_value_str(source, "url")?;
+
+        // 没有文件名时取链接路径的最后一段
+        let name = match get_value_str(block, "name") {
+            Ok(name) if !name.is_empty() => name,
+            _ => url.split(['?', '#']).next().unwrap_or_default().rsplit('/').next().unwrap_or_default().to_string(),
+        };
+
+        Ok(Media { url, hosted, expiry_time: get_value_str(source, "expiry_time").ok(), name })
+    }
+
+    /**
+     * 书签等只有链接的资源，名称默认为链接本身
+     */
+    pub fn from_url(url: &str) -> Self {
+        Media { url: url.to_string(), name: url.to_string(), ..Media::default() }
+    }
+
+    pub fn to_json(&self) -> Json {
+        json!({ "type": "external", "external": { "url": self.url } })
     }
 }
 
 
 #[derive(Debug)]
 pub struct BlockElement {
+    // 由Markdown等生成、尚未在Notion中创建的块没有ID
+    pub id: Option<BlockId>,
     pub line: Vec<FragmentText>,
     pub line_type: BlockType,
     pub color: AnnoColor,
     pub child: Vec<BlockElement>,
+    pub cells: Vec<Vec<FragmentText>>,
     pub status: Json,
+    pub media: Option<Media>,
+    // 标题的锚点，由Block::outline生成
+    pub anchor: String,
 }
 
 impl BlockElement {
-    fn from_type(line_type: BlockType) -> Self {
-        BlockElement { line: Vec::new(), line_type, color: AnnoColor::Default, child: Vec::new(), status: Json::default() }
+    pub fn from_type(line_type: BlockType) -> Self {
+        BlockElement::from_line(line_type, Vec::new())
+    }
+
+    pub fn from_text(line_type: BlockType, text: String) -> Self {
+        BlockElement::from_line(line_type, vec![ FragmentText { text, href: String::default(), annotation: Vec::new(), inline: Inline::Text } ])
     }
 
-    fn from_text(line_type: BlockType, text: String) -> Self {
+    pub fn from_line(line_type: BlockType, line: Vec<FragmentText>) -> Self {
         BlockElement {
-            line: vec![ FragmentText { text, href: String::default(), annotation: Vec::new() } ],
+            id: None,
+            line,
             line_type,
             color: AnnoColor::default(),
             child: Vec::new(),
+            cells: Vec::new(),
             status: Json::default(),
+            media: None,
+            anchor: String::default(),
         }
     }
 
+    /**
+     * 解析块并获取其子块，未解析的块作为Unsupported块保留
+     */
     pub fn new(value: &Json) -> Result<Self> {
+        BlockReader::default().element(value)
+    }
+
+    /**
+     * 由已获取的块对象生成，不再请求子块
+     */
+    pub fn from_object(object: &BlockObject, mode: ParseMode) -> Result<Self> {
+        BlockElement::parse(&serde_json::to_value(object)?, mode, || object.children.iter().map(|child| BlockElement::from_object(child, mode)).collect())
+    }
+
+    /**
+     * 解析块的Json，需要子块时由children获取
+     */
+    fn parse(value: &Json, mode: ParseMode, children: impl FnOnce() -> Result<Vec<BlockElement>>) -> Result<Self> {
+        let id: Option<BlockId> = Some(get_value_str(value, "id")?.parse()?);
+        let line_type = match BlockType::from_str(&get_value_str(value, "type")?) {
+            Ok(BlockType::Unsupported) | Err(_) => return BlockElement::unsupported(id, value, mode),
+            Ok(line_type) => line_type,
+        };
         let block = get_property_value(value, None)?;
-        let line_type = BlockType::from_str(&get_value_str(value, "type")?)?;
 
         match line_type {
-            BlockType::Divider => return Ok(BlockElement::from_type(line_type)),
-            BlockType::Equation => return Ok(BlockElement::from_text(line_type, get_value_str(block, "expression")?)),
+            BlockType::Divider => return Ok(BlockElement { id, ..BlockElement::from_type(line_type) }),
+            BlockType::Equation => return Ok(BlockElement { id, ..BlockElement::from_text(line_type, get_value_str(block, "expression")?) }),
+            // 子页面的子块是页面本身的内容，不在此展开
+            BlockType::ChildPage | BlockType::ChildDatabase => {
+                let url = id.clone().map(PageId::from).map(|id| id.url()).unwrap_or_default();
+                let line = vec![FragmentText::from_link(&get_value_str(block, "title")?, &url)];
+                return Ok(BlockElement { id, status: block.to_owned(), ..BlockElement::from_line(line_type, line) });
+            },
+            BlockType::LinkToPage => {
+                let url = get_value_str(block, "page_id").or_else(|_| get_value_str(block, "database_id"))?.parse::<PageId>()?.url();
+                let line = vec![FragmentText::from_link(&url, &url)];
+                return Ok(BlockElement { id, status: block.to_owned(), ..BlockElement::from_line(line_type, line) });
+            },
             _ => (),
         }
 
-        let rich_text = block.get("rich_text")
-            .ok_or(CommErr::UnsupportErr)?
-            .as_array().ok_or(CommErr::FormatErr("rich text"))?;
+        let media = match line_type {
+            _ if line_type.is_media() => Some(Media::new(block)?),
+            BlockType::Bookmark | BlockType::Embed | BlockType::LinkPreview => Some(Media::from_url(&get_value_str(block, "url")?)),
+            _ => None,
+        };
+        let line = match line_type.text_key() {
+            Some(key) => match block.get(key) {
+                Some(text) => FragmentText::line(text)?,
+                None => return BlockElement::unsupported(id, value, mode),
+            },
+            None => Vec::new(),
+        };
 
-        let mut line: Vec<FragmentText> = Vec::new();
-        for v in rich_text.iter() {
-            line.push(FragmentText::new(v)?);
+        let mut cells = Vec::new();
+        if let BlockType::TableRow = line_type {
+            for cell in block.get("cells").and_then(|c| c.as_array()).ok_or(CommErr::FormatErr("cells"))?.iter() {
+                cells.push(FragmentText::line(cell)?);
+            }
         }
 
         let color  = AnnoColor::from_str(&get_value_str(block, "color").unwrap_or_default()).unwrap_or_default();
 
-        // TODO: 异步
-        let mut child = Vec::new();
-        if value.get("has_children")
-            .ok_or(CommErr::FormatErr("has_children"))?
-            .as_bool().ok_or(CommErr::FormatErr("has_children"))?
-        {
-            let block = Notion::Blocks(get_value_str(value, "id")?).search::<Block>()?;
-            for be in block.inner.into_iter() {
-                child.push(be);
-            }
-        }
+        let child = children()?;
 
-        let status = {
-            use BlockType::*;
-            match line_type {
-                Heading1|Heading2|Heading3 => block.get("is_toggleable").ok_or(CommErr::FormatErr("is_toggleable"))?.to_owned(),
-                ToDo => block.get("checked").ok_or(CommErr::FormatErr("checked"))?.to_owned(),
-                Callout => block.get("icon").ok_or(CommErr::FormatErr("icon"))?.to_owned(),
-                Code => block.get("language").ok_or(CommErr::FormatErr("language"))?.to_owned(),
-                _ => Json::default(),
-            }
+        // 表格的宽度和表头设置、分栏的宽度比例整体保存
+        let status = match line_type.get_str("status") {
+            Some(key) => block.get(key).ok_or(CommErr::FormatErr(key))?.to_owned(),
+            None if matches!(line_type, BlockType::Table | BlockType::Column | BlockType::SyncedBlock) => block.to_owned(),
+            None => Json::default(),
         };
 
-        Ok(BlockElement { line, line_type, color, child, status })
+        Ok(BlockElement { id, line, line_type, color, child, cells, status, media, anchor: String::default() })
     }
-}
 
-impl FmtDisplay for BlockElement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut paragraph = String::default();
-        if self.line.is_empty() {
-            return write!(f, "\n<br/>");
-        } else {
-            for text in self.line.iter() {
-                paragraph += &text.to_string();
+    /**
+     * 按解析模式处理无法解析的块，宽松模式下不再获取其子块
+     */
+    fn unsupported(id: Option<BlockId>, value: &Json, mode: ParseMode) -> Result<Self> {
+        match mode {
+            ParseMode::Strict => Err(CommErr::UnsupportErr.into()),
+            ParseMode::Lenient => Ok(BlockElement { id, status: value.to_owned(), ..BlockElement::from_type(BlockType::Unsupported) }),
+        }
+    }
+
+    /**
+     * 未解析的块在Notion中的原始类型
+     */
+    pub fn unsupported_type(&self) -> Option<&str> {
+        match self.line_type {
+            BlockType::Unsupported => Some(self.status.get("type").and_then(|t| t.as_str()).unwrap_or("unsupported")),
+            _ => None,
+        }
+    }
+
+    /**
+     * 将指向同一导出中其他页面的链接改写为相对路径
+     */
+    pub fn relink(&mut self, pages: &HashMap<PageId, String>) {
+        for text in self.line.iter_mut().chain(self.cells.iter_mut().flatten()) {
+            if let Some(path) = PageId::from_href(&text.href).and_then(|id| pages.get(&id)) {
+                text.href = path.to_string();
             }
         }
 
-        let empty_val = Json::default();
-        let format = self.line_type.get_str("md").unwrap();
-        let status_to_replace = if self.status.is_null() {
-            ""
-        } else if self.status.is_boolean() {
-            if self.status.as_bool().unwrap() { "x" } else { " " }
-        } else if self.status.is_object() {
-            get_property_value(&self.status, None).unwrap_or(&empty_val).as_str().unwrap_or_default()
-        } else {
-            self.status.as_str().unwrap()
-        };
-        paragraph = format.replace("{}", &paragraph).replace("{status}", status_to_replace);
+        for child in self.child.iter_mut() {
+            child.relink(pages);
+        }
+    }
 
-        paragraph = match self.line_type {
-            BlockType::Callout => "\n".to_string() + &paragraph.replace("\n", "<br/>") + "\n",
-            BlockType::Quote => "\n".to_string() + &paragraph.replace("\n", "<br/>") + "\n",
-            BlockType::Heading1|BlockType::Heading2|BlockType::Heading3|BlockType::Code|BlockType::Toggle|BlockType::Equation|BlockType::Paragraph => "\n".to_string() + &paragraph + "\n",
-            _ => paragraph,
+    /**
+     * 查询内嵌的数据库，以表格作为其子块
+     * 无法查询的数据库（如未共享给集成）以Unsupported块代替，由Block::warnings报告原因
+     */
+    pub fn resolve_databases(&mut self, options: &TableOptions) {
+        if let (BlockType::ChildDatabase, Some(id)) = (&self.line_type, &self.id) {
+            self.child = vec![match database::table(&id.clone().into(), options) {
+                Ok(table) => table,
+                Err(err) => BlockElement {
+                    id: Some(id.clone()),
+                    status: json!({ "type": "child_database", "error": err.to_string() }),
+                    ..BlockElement::from_type(BlockType::Unsupported)
+                },
+            }];
+            return;
+        }
 
-        };
+        for child in self.child.iter_mut() {
+            child.resolve_databases(options);
+        }
+    }
 
-        if !self.child.is_empty() {
-            let mut child_paragraph = String::default();
-            for child in self.child.iter() {
-                child_paragraph = child_paragraph + &child.to_string();
+    /**
+     * 以离线提供的链接标题（链接 => 标题）命名书签、嵌入和链接预览
+     */
+    pub fn titles(&mut self, titles: &HashMap<String, String>) {
+        if let (true, Some(media)) = (!self.line_type.is_media(), self.media.as_mut()) {
+            if let Some(title) = titles.get(&media.url) {
+                media.name = title.to_string();
             }
-            child_paragraph = child_paragraph.trim_start().to_string();
+        }
 
-            paragraph = match self.line_type {
-                BlockType::Toggle => paragraph.replace("{child}", &child_paragraph),
-                BlockType::Quote => paragraph.trim_end().to_string() + "\n>" + &child_paragraph.trim_start(),
-                BlockType::Heading1|BlockType::Heading2|BlockType::Heading3 => paragraph + &child_paragraph,
-                _ => paragraph.trim_end().to_string() + "\n\t" + &child_paragraph.replace("\n\n", "\n").replace("\n", "\n\t"),
-            };
+        for child in self.child.iter_mut() {
+            child.titles(titles);
+        }
+    }
+
+    /**
+     * 表格的列数、是否以首行为表头、是否以首列为表头
+     */
+    pub fn table_header(&self) -> (usize, bool, bool) {
+        let flag = |key: &str| self.status.get(key).and_then(|v| v.as_bool()).unwrap_or_default();
+        let width = self.status.get("table_width").and_then(|v| v.as_u64()).unwrap_or_default() as usize;
+        (width, flag("has_column_header"), flag("has_row_header"))
+    }
+
+    /**
+     * 块列表（包括子块）中未能解析的块，每个块一条警告，流式写出时可逐页检查
+     */
+    pub fn warnings(list: &[BlockElement]) -> Vec<String> {
+        let mut blocks = UnsupportedBlocks::default();
+        render::walk(list, &mut blocks);
+        blocks.0
+    }
+
+    /**
+     * 可以通过API创建的块，未解析的块直接跳过
+     */
+    pub fn creatable(list: &[BlockElement]) -> Vec<&BlockElement> {
+        list.iter().filter(|be| be.unsupported_type().is_none()).collect()
+    }
+
+    /**
+     * 块的Json形式是否已包含全部子块（表格行必须随表格一同创建）
+     */
+    pub fn child_inline(&self) -> bool {
+        match self.line_type {
+            BlockType::Table | BlockType::ColumnList | BlockType::Column => true,
+            // 目录的子块由标题生成，不需要创建
+            BlockType::TableOfContents => true,
+            // 引用的同步块只需创建引用，内容属于原始块
+            BlockType::SyncedBlock => self.synced_source() != self.id,
+            _ => false,
         }
+    }
+
+    /**
+     * 子页面或页面链接所指向的页面ID
+     */
+    pub fn page_target(&self) -> Option<PageId> {
+        match self.line_type {
+            BlockType::ChildPage => self.id.clone().map(PageId::from),
+            BlockType::LinkToPage => self.status.get("page_id").and_then(|id| id.as_str()).and_then(|id| id.parse().ok()),
+            _ => None,
+        }
+    }
+
+    /**
+     * 同步块内容的来源块ID，原始同步块的来源为自身
+     */
+    pub fn synced_source(&self) -> Option<BlockId> {
+        match self.line_type {
+            BlockType::SyncedBlock => self.status.get("synced_from")
+                .and_then(|from| from.get("block_id"))
+                .and_then(|id| id.as_str())
+                .and_then(|id| id.parse().ok())
+                .or_else(|| self.id.clone()),
+            _ => None,
+        }
+    }
 
-        paragraph = match self.color {
-            AnnoColor::Default => paragraph,
+    /**
+     * 生成Notion API追加块时所需的Json，子块需另行追加（表格行除外）
+     */
+    pub fn to_json(&self) -> Json {
+        let rich_text = |line: &Vec<FragmentText>| line.iter().flat_map(|t| t.to_json()).collect::<Vec<Json>>();
+
+        let mut body = Map::new();
+        match self.line_type {
+            BlockType::Divider => (),
+            BlockType::Equation => {
+                body.insert("expression".to_string(), json!(self.line.iter().map(|t| t.text.as_str()).collect::<String>()));
+            },
+            // 随块创建的子块同样受数量限制，表格多出的行在创建后追加
+            BlockType::Table | BlockType::ColumnList | BlockType::Column => {
+                body = self.status.as_object().cloned().unwrap_or_default();
+                body.insert("children".to_string(), json!(BlockElement::creatable(&self.child).iter().take(APPEND_LIMIT).map(|be| be.to_json()).collect::<Vec<Json>>()));
+            },
+            BlockType::Image|BlockType::Video|BlockType::File|BlockType::Pdf|BlockType::Audio => {
+                if let Some(Json::Object(media)) = self.media.as_ref().map(|m| m.to_json()) {
+                    body = media;
+                }
+                body.insert("caption".to_string(), json!(rich_text(&self.line)));
+            },
+            // 链接预览无法通过API创建，改为书签
+            BlockType::Bookmark | BlockType::Embed | BlockType::LinkPreview => {
+                body.insert("url".to_string(), json!(self.media.as_ref().map(|m| m.url.as_str()).unwrap_or_default()));
+                body.insert("caption".to_string(), json!(rich_text(&self.line)));
+            },
+            // 子页面无法作为块创建，改为页面链接
+            BlockType::ChildPage => {
+                body.insert("type".to_string(), json!("page_id"));
+                body.insert("page_id".to_string(), json!(self.id));
+            },
+            BlockType::ChildDatabase => {
+                body.insert("type".to_string(), json!("database_id"));
+                body.insert("database_id".to_string(), json!(self.id));
+            },
+            BlockType::LinkToPage => {
+                body = self.status.as_object().cloned().unwrap_or_default();
+            },
+            BlockType::SyncedBlock => {
+                body.insert("synced_from".to_string(), match self.child_inline() {
+                    true => json!({ "type": "block_id", "block_id": self.synced_source() }),
+                    false => Json::Null,
+                });
+            },
+            BlockType::TableOfContents => {
+                body.insert("color".to_string(), json!(self.color.to_string()));
+            },
+            BlockType::TableRow => {
+                body.insert("cells".to_string(), json!(self.cells.iter().map(rich_text).collect::<Vec<Vec<Json>>>()));
+            },
             _ => {
-                let color_format = Annotation::Color(AnnoColor::Default).get_str("md").unwrap();
-                "\n".to_string() + &color_format.replace("{}", &paragraph).replace("{color}", self.color.get_str("md").unwrap()) + "\n"
+                body.insert("rich_text".to_string(), json!(rich_text(&self.line)));
+                if !matches!(self.line_type, BlockType::Code) {
+                    body.insert("color".to_string(), json!(self.color.to_string()));
+                }
+                if let Some(key) = self.line_type.get_str("status") {
+                    if !self.status.is_null() {
+                        body.insert(key.to_string(), self.status.to_owned());
+                    }
+                }
             },
+        }
+
+        let line_type = match self.line_type {
+            BlockType::LinkPreview => BlockType::Bookmark.to_string(),
+            BlockType::ChildPage | BlockType::ChildDatabase => BlockType::LinkToPage.to_string(),
+            _ => self.line_type.to_string(),
+        };
+        json!({ "object": "block", "type": line_type, line_type.as_str(): body })
+    }
+}
+
+/**
+ * 依次为标题生成锚点，重复的锚点加上序号，返回各标题的层级和指向锚点的链接
+ */
+fn anchors(list: &mut [BlockElement], used: &mut HashSet<String>, headings: &mut Vec<(usize, FragmentText)>) {
+    for be in list.iter_mut() {
+        let level = match be.line_type {
+            BlockType::Heading1 => 1,
+            BlockType::Heading2 => 2,
+            BlockType::Heading3 => 3,
+            _ => 0,
         };
 
-        write!(f, "{}", paragraph)
+        if level > 0 {
+            let text: String = be.line.iter().map(|t| t.inline.text().unwrap_or_else(|| t.text.to_string())).collect();
+            let slug = render::slug(&text);
+            let mut anchor = slug.to_string();
+            let mut index = 0;
+            while used.contains(&anchor) {
+                index += 1;
+                anchor = format!("{}-{}", slug, index);
+            }
+            used.insert(anchor.to_string());
+            headings.push((level, FragmentText::from_link(&text, &("#".to_string() + &anchor))));
+            be.anchor = anchor;
+        }
+
+        if !matches!(be.line_type, BlockType::TableOfContents) {
+            anchors(&mut be.child, used, headings);
+        }
     }
 }
 
+/**
+ * 将标题按层级嵌套为无序列表，缺少上一级标题时挂在最近的上级之下
+ */
+fn contents(headings: &[(usize, FragmentText)]) -> Vec<BlockElement> {
+    let mut items: Vec<BlockElement> = Vec::new();
+    for (level, link) in headings.iter() {
+        let mut list = &mut items;
+        for _ in 1..*level {
+            if list.is_empty() {
+                break;
+            }
+            list = &mut list.last_mut().unwrap().child;
+        }
+        list.push(BlockElement::from_line(BlockType::BulletedListItem, vec![link.clone()]));
+    }
 
-#[derive(Debug)]
+    items
+}
+
+/**
+ * 以标题列表填充页面中的目录块
+ */
+fn fill_contents(list: &mut [BlockElement], headings: &[(usize, FragmentText)]) {
+    for be in list.iter_mut() {
+        match be.line_type {
+            BlockType::TableOfContents => be.child = contents(headings),
+            _ => fill_contents(&mut be.child, headings),
+        }
+    }
+}
+
+/**
+ * 逐批为标题生成锚点并记录标题，批次之间的锚点不重复
+ */
+#[derive(Debug, Default)]
+pub(crate) struct Outline {
+    used: HashSet<String>,
+    headings: Vec<(usize, FragmentText)>,
+}
+
+impl Outline {
+    pub(crate) fn anchors(&mut self, list: &mut [BlockElement]) {
+        anchors(list, &mut self.used, &mut self.headings);
+    }
+
+    /**
+     * 以目前记录的全部标题填充目录块
+     */
+    pub(crate) fn contents(&self, list: &mut [BlockElement]) {
+        fill_contents(list, &self.headings);
+    }
+}
+
+/**
+ * 块列表中是否有目录块
+ */
+pub(crate) fn has_contents(list: &[BlockElement]) -> bool {
+    list.iter().any(|be| matches!(be.line_type, BlockType::TableOfContents) || has_contents(&be.child))
+}
+
+/**
+ * 收集未解析的块
+ */
+#[derive(Default)]
+struct UnsupportedBlocks(Vec<String>);
+
+impl Visitor for UnsupportedBlocks {
+    fn enter(&mut self, be: &BlockElement, _depth: usize) -> bool {
+        if let Some(block_type) = be.unsupported_type() {
+            let mut warning = format!("Block [{}] ({}) is not supported", block_type, be.id.as_deref().unwrap_or_default());
+            if let Some(err) = be.status.get("error").and_then(|e| e.as_str()) {
+                warning = warning + ": " + err;
+            }
+            self.0.push(warning);
+        }
+        true
+    }
+}
+
+impl FmtDisplay for BlockElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Markdown::default().block(self, f)
+    }
+}
+
+
+#[derive(Debug, Default)]
 pub struct Block {
     pub inner: Vec<BlockElement>
 }
 
+impl Block {
+    /**
+     * 由已获取的块对象生成，可在修改块对象后再输出
+     */
+    pub fn from_objects(list: &[BlockObject], mode: ParseMode) -> Result<Self> {
+        Ok(Block { inner: list.iter().map(|object| BlockElement::from_object(object, mode)).collect::<Result<Vec<BlockElement>>>()? })
+    }
+
+    pub fn from_markdown(text: &str) -> Self {
+        Block { inner: import::parse(text) }
+    }
+
+    pub fn render<R: Renderer + ?Sized>(&self, renderer: &R) -> String {
+        renderer.document(&self.inner)
+    }
+
+    /**
+     * 逐块渲染并写入fmt::Write
+     */
+    pub fn stream<R: Renderer + ?Sized, W: fmt::Write>(&self, renderer: &R, out: &mut W) -> fmt::Result {
+        renderer.blocks(&self.inner, out)
+    }
+
+    /**
+     * 逐块渲染并写入io::Write，适合直接输出到文件
+     */
+    pub fn write<R: Renderer + ?Sized, W: io::Write + ?Sized>(&self, renderer: &R, out: &mut W) -> io::Result<()> {
+        render::write(renderer, &self.inner, out)
+    }
+
+    pub fn relink(&mut self, pages: &HashMap<PageId, String>) {
+        for be in self.inner.iter_mut() {
+            be.relink(pages);
+        }
+    }
+
+    pub fn resolve_databases(&mut self, options: &TableOptions) {
+        for be in self.inner.iter_mut() {
+            be.resolve_databases(options);
+        }
+    }
+
+    pub fn titles(&mut self, titles: &HashMap<String, String>) {
+        for be in self.inner.iter_mut() {
+            be.titles(titles);
+        }
+    }
+
+    /**
+     * 为标题生成不重复的锚点，并以页面的标题列表填充目录块
+     */
+    pub fn outline(&mut self) {
+        let mut outline = Outline::default();
+        outline.anchors(&mut self.inner);
+        outline.contents(&mut self.inner);
+    }
+
+    /**
+     * 宽松模式下未能解析的块和无法查询的内嵌数据库，每个块一条警告
+     */
+    pub fn warnings(&self) -> Vec<String> {
+        BlockElement::warnings(&self.inner)
+    }
+
+    pub fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        render::walk(&self.inner, visitor)
+    }
+}
+
 impl NewImp for Block {
     fn new(val: &Json) -> Result<Self> {
         let val = val.as_array().ok_or(CommErr::FormatErr("results"))?;
+        let mut reader = BlockReader::default();
         let mut inner = Vec::new();
         for val_arr in val.iter() {
-            inner.push(BlockElement::new(val_arr)?);
+            inner.push(reader.element(val_arr)?);
         }
 
         Ok(Block { inner })
@@ -216,17 +882,61 @@ impl NewImp for Block {
 
 impl FmtDisplay for Block {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut output = String::default();
-        for block in self.inner.iter() {
-            output = output.trim_end().to_string() + "\n" + &block.to_string();
-        }
-
-        write!(f, "{}", output.trim())
+        self.stream(&Markdown::default(), f)
     }
 }
 
-impl Default for Block {
-    fn default() -> Self {
-        Block { inner: Vec::new() }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SOURCE: &str = "59833787-2cf9-4fdf-8782-e53db20768a5";
+
+    fn block(block_type: &str, content: Json) -> Json {
+        json!({
+            "object": "block",
+            "id": "c02fc1d3-db8b-45c5-a222-27595b15aea7",
+            "has_children": false,
+            "type": block_type,
+            block_type: content,
+        })
+    }
+
+    #[test]
+    fn synced_source_not_expanded_twice() {
+        let mut reader = BlockReader { syncing: vec![SOURCE.parse().unwrap()], ..BlockReader::default() };
+        let value = block("synced_block", json!({ "synced_from": { "type": "block_id", "block_id": SOURCE } }));
+
+        let be = reader.element(&value).unwrap();
+        assert_eq!(be.line_type, BlockType::SyncedBlock);
+        assert!(be.child.is_empty());
+        assert_eq!(reader.syncing.len(), 1);
+    }
+
+    #[test]
+    fn unknown_block_by_mode() {
+        let value = block("ai_block", json!({}));
+
+        let be = BlockReader::new(ParseMode::Lenient).element(&value).unwrap();
+        assert_eq!(be.line_type, BlockType::Unsupported);
+        assert_eq!(be.unsupported_type(), Some("ai_block"));
+        assert!(BlockReader::new(ParseMode::Strict).element(&value).is_err());
+    }
+
+    #[test]
+    fn columns_over_limit() {
+        let mut column = BlockElement::from_type(BlockType::Column);
+        column.child = (0..150).map(|i| BlockElement::from_text(BlockType::Paragraph, i.to_string())).collect();
+        column.child[0].child.push(BlockElement::from_text(BlockType::Paragraph, "nested".to_string()));
+        let mut columns = BlockElement::from_type(BlockType::ColumnList);
+        columns.child.push(column);
+        assert!(columns.child_inline());
+
+        // 超出数量限制的子块和子块的子块在分栏创建后追加
+        let json = columns.to_json();
+        let children = json["column_list"]["children"][0]["column"]["children"].as_array().unwrap();
+        assert_eq!(children.len(), 100);
+        assert!(children[0]["paragraph"].get("children").is_none());
     }
 }
@@ -1,5 +1,7 @@
-use super::{page::Page, Json, CommErr, NewImp};
+use super::{Notion, NotionBuilder, page::{Page, PageSchema}, id::{PageId, DatabaseId}, tree::{file_name, unique_name}, block::{BlockElement, FragmentText}, text::{BlockType, Inline}, render::encode_path, get_property_value, Json, CommErr, NewImp};
 use anyhow::Result;
+use serde_json::{json, Map};
+use std::collections::{HashMap, HashSet};
 
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -10,13 +12,293 @@ pub struct Database {
 
 impl NewImp for Database {
     fn new(list: &Json) -> Result<Self> {
+        Database::with_schema(list, &PageSchema::default())
+    }
+}
+
+impl Database {
+    /**
+     * 按属性映射解析查询结果中的页面
+     */
+    pub fn with_schema(list: &Json, schema: &PageSchema) -> Result<Self> {
         let list = list.as_array().ok_or(CommErr::FormatErr("results"))?;
 
         let mut page_list = Vec::new();
         for page in list.iter() {
-            page_list.push(Page::new(page)?);
+            page_list.push(Page::with_schema(page, schema)?);
         }
 
         Ok(Database { page_list })
     }
-}
\ No newline at end of file
+
+    /**
+     * 导出时各页面对应的相对路径，用于Block::relink
+     * 文件名与页面树导出时的规则相同：由标题生成合法的文件名，重复时加上序号
+     */
+    pub fn links(&self, extension: &str) -> HashMap<PageId, String> {
+        let mut names = HashSet::new();
+        self.page_list.iter().map(|page| {
+            let name = unique_name(file_name(&page.title), &mut names);
+            (page.id.clone(), encode_path(&(name + "." + extension)))
+        }).collect()
+    }
+}
+
+
+/**
+ * 内嵌数据库输出为表格时的列和行数限制，未指定列时输出全部属性
+ */
+#[derive(Debug, Default, Clone)]
+pub struct TableOptions {
+    columns: Vec<String>,
+    limit: Option<usize>,
+}
+
+impl TableOptions {
+    /**
+     * 按给定顺序输出的属性名，数据库中不存在的属性会导致查询失败
+     * API不提供视图中的列顺序，要与界面一致时需在此列出
+     */
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/**
+ * 查询数据库的行并生成表格块
+ * 未指定列时标题列在最前，其余列按API返回的属性顺序排列，与Notion界面中视图的列顺序不一定相同，需要固定顺序时用TableOptions::columns指定
+ */
+pub fn table(id: &DatabaseId, options: &TableOptions) -> Result<BlockElement> {
+    let schema = Notion::Databases(id.clone()).retrieve()?;
+    let properties = schema.get("properties").and_then(|p| p.as_object()).ok_or(CommErr::FormatErr("properties"))?;
+    let columns = columns(properties, options)?;
+    let title = properties.iter()
+        .find(|(_, p)| p.get("type").and_then(|t| t.as_str()) == Some("title"))
+        .map(|(name, _)| name);
+    let rows = rows(&columns, title, &NotionBuilder::new(Notion::Databases(id.clone())).results(options.limit)?)?;
+
+    Ok(BlockElement {
+        id: Some(id.clone().into()),
+        child: rows,
+        status: json!({ "table_width": columns.len(), "has_column_header": true, "has_row_header": false }),
+        ..BlockElement::from_type(BlockType::Table)
+    })
+}
+
+/**
+ * 输出的列，未指定时标题列在最前；指定的列不存在时返回错误，以免生成缺列或没有列的表格
+ */
+fn columns<'a>(properties: &'a Map<String, Json>, options: &'a TableOptions) -> Result<Vec<&'a String>> {
+    if let Some(name) = options.columns.iter().find(|name| !properties.contains_key(*name)) {
+        return Err(CommErr::PropertyErr(name.to_string()).into());
+    }
+    if !options.columns.is_empty() {
+        return Ok(options.columns.iter().collect());
+    }
+
+    let mut columns: Vec<&String> = properties.keys().collect();
+    columns.sort_by_key(|name| properties[*name].get("type").and_then(|t| t.as_str()) != Some("title"));
+    Ok(columns)
+}
+
+/**
+ * 表头和各行，标题列的文本链接到对应的页面
+ */
+fn rows(columns: &[&String], title: Option<&String>, list: &[Json]) -> Result<Vec<BlockElement>> {
+    let mut rows = vec![BlockElement {
+        cells: columns.iter().map(|name| vec![FragmentText::from_text(name)]).collect(),
+        ..BlockElement::from_type(BlockType::TableRow)
+    }];
+    for row in list.iter() {
+        let mut cells = Vec::new();
+        for name in columns.iter() {
+            let cell = match row.get("properties").and_then(|p| p.get(name.as_str())) {
+                Some(value) => property_cell(value)?,
+                None => Vec::new(),
+            };
+            cells.push(cell);
+        }
+
+        let page = row.get("id").and_then(|id| id.as_str()).and_then(|id| id.parse::<PageId>().ok());
+        let index = columns.iter().position(|name| Some(*name) == title);
+        if let (Some(cell), Some(page)) = (index.and_then(|index| cells.get_mut(index)), page) {
+            for text in cell.iter_mut().filter(|t| t.href.is_empty()) {
+                text.href = page.url();
+            }
+        }
+        rows.push(BlockElement { cells, ..BlockElement::from_type(BlockType::TableRow) });
+    }
+
+    Ok(rows)
+}
+
+/**
+ * 属性值转为单元格，标题和文本保留样式，其他类型转为纯文本
+ */
+fn property_cell(value: &Json) -> Result<Vec<FragmentText>> {
+    match value.get("type").and_then(|t| t.as_str()) {
+        Some("title") | Some("rich_text") => FragmentText::line(get_property_value(value, None)?),
+        _ => Ok(match property_text(value) {
+            text if text.is_empty() => Vec::new(),
+            text => vec![FragmentText::from_text(&text)],
+        }),
+    }
+}
+
+/**
+ * 属性值的纯文本形式，公式和汇总取其结果
+ */
+pub fn property_text(value: &Json) -> String {
+    let data = match get_property_value(value, None) {
+        Ok(data) if !data.is_null() => data,
+        _ => return String::default(),
+    };
+    let field = |val: &Json, key: &str| val.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let join = |list: Vec<String>| list.into_iter().filter(|s| !s.is_empty()).collect::<Vec<String>>().join(", ");
+    let array = || data.as_array().cloned().unwrap_or_default();
+
+    match value.get("type").and_then(|t| t.as_str()).unwrap_or_default() {
+        "title" | "rich_text" => array().iter().map(|t| field(t, "plain_text")).collect(),
+        "number" => data.to_string(),
+        "checkbox" => if data.as_bool().unwrap_or_default() { "✓".to_string() } else { String::default() },
+        "select" | "status" | "created_by" | "last_edited_by" => field(data, "name"),
+        "multi_select" | "people" | "files" => join(array().iter().map(|v| field(v, "name")).collect()),
+        "relation" => join(array().iter().map(|v| field(v, "id")).collect()),
+        "date" => Inline::Date { start: field(data, "start"), end: data.get("end").and_then(|e| e.as_str()).map(|e| e.to_string()) }
+            .text().unwrap_or_default(),
+        "formula" => property_text(data),
+        "rollup" => match data.get("type").and_then(|t| t.as_str()) {
+            Some("array") => join(data.get("array").and_then(|a| a.as_array()).cloned().unwrap_or_default().iter().map(property_text).collect()),
+            _ => property_text(data),
+        },
+        "unique_id" => match data.get("prefix").and_then(|p| p.as_str()) {
+            Some(prefix) => format!("{}-{}", prefix, data.get("number").unwrap_or(&Json::Null)),
+            None => data.get("number").map(|n| n.to_string()).unwrap_or_default(),
+        },
+        "string" => data.as_str().unwrap_or_default().to_string(),
+        "boolean" => data.as_bool().map(|b| b.to_string()).unwrap_or_default(),
+        _ => data.as_str().map(|s| s.to_string()).unwrap_or_default(),
+    }
+}
+
+/**
+ * 属性值的Json形式，数字和复选框保留类型，多选、人员等为字符串数组，日期区间为含start和end的对象，空值为Null
+ */
+pub fn property_value(value: &Json) -> Json {
+    let data = match get_property_value(value, None) {
+        Ok(data) if !data.is_null() => data,
+        _ => return Json::Null,
+    };
+    let field = |val: &Json, key: &str| val.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let list = |key: &str| Json::Array(
+        data.as_array().cloned().unwrap_or_default().iter()
+            .map(|v| field(v, key))
+            .filter(|s| !s.is_empty())
+            .map(Json::String)
+            .collect()
+    );
+
+    match value.get("type").and_then(|t| t.as_str()).unwrap_or_default() {
+        "number" | "checkbox" | "boolean" | "string" => data.to_owned(),
+        "multi_select" | "people" | "files" => list("name"),
+        "relation" => list("id"),
+        "date" => match data.get("end").and_then(|e| e.as_str()) {
+            Some(end) => json!({ "start": field(data, "start"), "end": end }),
+            None => json!(field(data, "start")),
+        },
+        "formula" => property_value(data),
+        "rollup" => match data.get("type").and_then(|t| t.as_str()) {
+            Some("array") => Json::Array(data.get("array").and_then(|a| a.as_array()).cloned().unwrap_or_default().iter().map(property_value).collect()),
+            _ => property_value(data),
+        },
+        "unique_id" if data.get("prefix").is_none_or(|p| p.is_null()) => data.get("number").cloned().unwrap_or_default(),
+        _ => match property_text(value) {
+            text if text.is_empty() => Json::Null,
+            text => Json::String(text),
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notion::text::Annotation;
+
+    const PAGE: &str = "59833787-2cf9-4fdf-8782-e53db20768a5";
+
+    fn rich_text(text: &str, bold: bool) -> Json {
+        json!({
+            "type": "text",
+            "text": { "content": text, "link": null },
+            "annotations": { "bold": bold, "italic": false, "strikethrough": false, "underline": false, "code": false, "color": "default" },
+            "plain_text": text,
+            "href": null,
+        })
+    }
+
+    fn properties() -> Map<String, Json> {
+        json!({
+            "Tags": { "id": "a", "type": "multi_select", "multi_select": [{ "name": "rust" }, { "name": "notion" }] },
+            "Name": { "id": "title", "type": "title", "title": [rich_text("Kale", true)] },
+            "Price": { "id": "b", "type": "number", "number": 2.5 },
+        }).as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn property_texts() {
+        let properties = properties();
+        assert_eq!(property_text(&properties["Tags"]), "rust, notion");
+        assert_eq!(property_text(&properties["Name"]), "Kale");
+        assert_eq!(property_text(&properties["Price"]), "2.5");
+        assert_eq!(property_text(&json!({ "type": "checkbox", "checkbox": true })), "✓");
+        assert_eq!(property_text(&json!({ "type": "unique_id", "unique_id": { "prefix": "TASK", "number": 7 } })), "TASK-7");
+        assert_eq!(property_text(&json!({ "type": "formula", "formula": { "type": "string", "string": "ok" } })), "ok");
+        assert_eq!(property_text(&json!({ "type": "select", "select": null })), "");
+    }
+
+    #[test]
+    fn property_cells() {
+        let properties = properties();
+        let title = property_cell(&properties["Name"]).unwrap();
+        assert_eq!(title[0].text, "Kale");
+        assert!(title[0].annotation.contains(&Annotation::Bold));
+
+        let tags = property_cell(&properties["Tags"]).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert!(!tags[0].annotation.contains(&Annotation::Bold));
+        assert!(property_cell(&json!({ "type": "rich_text", "rich_text": [] })).unwrap().is_empty());
+    }
+
+    #[test]
+    fn column_order() {
+        let properties = properties();
+        assert_eq!(columns(&properties, &TableOptions::default()).unwrap(), ["Name", "Tags", "Price"]);
+
+        let options = TableOptions::default().columns(&["Price", "Name"]);
+        assert_eq!(columns(&properties, &options).unwrap(), ["Price", "Name"]);
+
+        let options = TableOptions::default().columns(&["Price", "Missing"]);
+        assert!(columns(&properties, &options).is_err());
+    }
+
+    #[test]
+    fn title_column_linked() {
+        let properties = properties();
+        let options = TableOptions::default().columns(&["Price", "Name"]);
+        let columns = columns(&properties, &options).unwrap();
+        let row = json!({ "id": PAGE, "properties": properties });
+
+        let rows = rows(&columns, Some(&"Name".to_string()), &[row]).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].cells[0][0].text, "Price");
+        assert!(rows[1].cells[0][0].href.is_empty());
+        assert_eq!(rows[1].cells[1][0].href, PAGE.parse::<PageId>().unwrap().url());
+    }
+}
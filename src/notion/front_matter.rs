@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+use strum::EnumProperty;
+use strum_macros::{Display as EnumDisplay, EnumString};
+
+use super::{page::Page, database::property_value, Json};
+
+
+/**
+ * front matter的格式，fence为首尾的分隔行
+ */
+#[derive(EnumDisplay, EnumString, EnumProperty, Debug, Clone, Copy, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum MatterFormat {
+    #[strum(props(fence="---", assign=": "))]
+    Yaml,
+    #[strum(props(fence="+++", assign=" = "))]
+    Toml,
+}
+
+
+/**
+ * 由页面元数据生成的front matter
+ * 固定输出title、id、created_time、edited_time和author，之后是页面的全部属性，值为空的字段不输出
+ * 键重复时（如属性名为title，或改名后与已有的键相同）先出现的字段保留原键，之后的加上序号
+ */
+#[derive(Debug, Clone)]
+pub struct FrontMatter {
+    format: MatterFormat,
+    rename: HashMap<String, String>,
+    exclude: HashSet<String>,
+}
+
+impl FrontMatter {
+    pub fn new(format: MatterFormat) -> Self {
+        FrontMatter { format, rename: HashMap::new(), exclude: HashSet::new() }
+    }
+
+    /**
+     * 将字段name（属性名或固定字段）输出为key
+     */
+    pub fn rename(mut self, name: &str, key: &str) -> Self {
+        self.rename.insert(name.to_string(), key.to_string());
+        self
+    }
+
+    /**
+     * 不输出的字段
+     */
+    pub fn exclude(mut self, names: &[&str]) -> Self {
+        self.exclude.extend(names.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /**
+     * 按输出顺序排列的键和值，已按设置改名和排除
+     */
+    pub fn fields(&self, page: &Page) -> Vec<(String, Json)> {
+        let text = |s: &str| if s.is_empty() { Json::Null } else { Json::String(s.to_string()) };
+        let mut fields = vec![
+            ("title".to_string(), text(&page.title)),
+            ("id".to_string(), text(&page.id)),
+            ("created_time".to_string(), text(&page.created_time)),
+            ("edited_time".to_string(), text(&page.edited_time)),
+            ("author".to_string(), text(page.author.name())),
+        ];
+        fields.extend(page.properties.iter().map(|p| (p.property.get_val(), property_value(&p.value))));
+
+        let fields = fields.into_iter()
+            .filter(|(name, value)| !value.is_null() && !self.exclude.contains(name))
+            .map(|(name, value)| (self.rename.get(&name).cloned().unwrap_or(name), value));
+        unique_keys(fields)
+    }
+
+    /**
+     * 生成页面的front matter，之后空一行
+     */
+    pub fn render(&self, page: &Page) -> String {
+        self.format(&self.fields(page))
+    }
+
+    /**
+     * 输出给定的字段，首尾加上分隔行
+     */
+    pub fn format(&self, fields: &[(String, Json)]) -> String {
+        let fence = self.format.get_str("fence").unwrap_or_default();
+        let assign = self.format.get_str("assign").unwrap_or_default();
+        let mut output = fence.to_string() + "\n";
+        for (name, value) in unique_keys(fields.iter().filter(|(_, value)| !value.is_null()).cloned()) {
+            output = output + &key(&name) + assign + &self.value(&value) + "\n";
+        }
+
+        output + fence + "\n\n"
+    }
+
+    /**
+     * 数组和对象都使用行内写法，字符串按Json转义（YAML和TOML的双引号字符串都兼容Json的转义）
+     */
+    fn value(&self, value: &Json) -> String {
+        match value {
+            Json::Array(list) => {
+                let items: Vec<String> = list.iter().filter(|v| !v.is_null()).map(|v| self.value(v)).collect();
+                "[".to_string() + &items.join(", ") + "]"
+            },
+            Json::Object(map) => {
+                let assign = self.format.get_str("assign").unwrap_or_default();
+                let items: Vec<String> = map.iter()
+                    .filter(|(_, v)| !v.is_null())
+                    .map(|(k, v)| key(k) + assign + &self.value(v))
+                    .collect();
+                "{ ".to_string() + &items.join(", ") + " }"
+            },
+            _ => value.to_string(),
+        }
+    }
+}
+
+/**
+ * 重复的键依次改为key_2、key_3……
+ */
+fn unique_keys(fields: impl Iterator<Item = (String, Json)>) -> Vec<(String, Json)> {
+    let mut used = HashSet::new();
+    fields.map(|(name, value)| {
+        let mut unique = name.to_string();
+        let mut n = 1;
+        while !used.insert(unique.to_string()) {
+            n += 1;
+            unique = format!("{}_{}", name, n);
+        }
+        (unique, value)
+    }).collect()
+}
+
+/**
+ * 只含字母、数字、下划线和连字符的键不加引号
+ */
+fn key(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        name.to_string()
+    } else {
+        Json::String(name.to_string()).to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use super::super::NewImp;
+
+    fn page() -> Page {
+        Page::new(&json!({
+            "object": "page",
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "created_time": "2023-05-01T09:30:00.000Z",
+            "last_edited_time": "2023-05-02T10:00:00.000Z",
+            "created_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "last_edited_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "cover": null,
+            "icon": null,
+            "archived": false,
+            "url": "https://www.notion.so/Tuscan-kale-598337872cf94fdf8782e53db20768a5",
+            "properties": {
+                "Name": { "id": "title", "type": "title", "title": [{ "type": "text", "text": { "content": "Tuscan kale" }, "plain_text": "Tuscan kale" }] },
+                "title": { "id": "a%3Ab", "type": "rich_text", "rich_text": [{ "type": "text", "text": { "content": "subtitle" }, "plain_text": "subtitle" }] },
+                "Status": { "id": "c%3Ad", "type": "select", "select": { "id": "1", "name": "Done", "color": "green" } },
+                "Empty": { "id": "e%3Af", "type": "rich_text", "rich_text": [] }
+            }
+        })).unwrap()
+    }
+
+    #[test]
+    fn fixed_fields_first() {
+        let fields = FrontMatter::new(MatterFormat::Yaml).fields(&page());
+        let keys: Vec<&str> = fields.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["title", "id", "created_time", "edited_time", "title_2", "Status"]);
+        assert_eq!(fields[0].1, json!("Tuscan kale"));
+        assert_eq!(fields[4].1, json!("subtitle"));
+    }
+
+    #[test]
+    fn rename_onto_existing_key() {
+        let matter = FrontMatter::new(MatterFormat::Yaml).rename("Status", "id").exclude(&["title"]);
+        let keys: Vec<String> = matter.fields(&page()).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["id", "created_time", "edited_time", "id_2"]);
+    }
+
+    #[test]
+    fn format_yaml_and_toml() {
+        let fields = vec![
+            ("title".to_string(), json!("a \"quoted\" title")),
+            ("tags".to_string(), json!(["x", null, "y"])),
+            ("Due date".to_string(), json!({ "start": "2023-05-01", "end": null })),
+            ("skip".to_string(), Json::Null),
+            ("title".to_string(), json!("again")),
+        ];
+        assert_eq!(FrontMatter::new(MatterFormat::Yaml).format(&fields),
+            "---\ntitle: \"a \\\"quoted\\\" title\"\ntags: [\"x\", \"y\"]\n\"Due date\": { start: \"2023-05-01\" }\ntitle_2: \"again\"\n---\n\n");
+        assert_eq!(FrontMatter::new(MatterFormat::Toml).format(&fields[..2]),
+            "+++\ntitle = \"a \\\"quoted\\\" title\"\ntags = [\"x\", \"y\"]\n+++\n\n");
+    }
+}
@@ -0,0 +1,298 @@
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use super::CommErr;
+
+
+/**
+ * Notion对象的ID，统一保存为带连字符的小写UUID
+ * 可由带或不带连字符的UUID，以及notion.so/notion.site上的分享链接解析得到
+ */
+macro_rules! notion_id {
+    ($name:ident) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /**
+             * 不含连字符的形式，与Notion网址中的写法一致
+             */
+            pub fn simple(&self) -> String {
+                self.0.replace('-', "")
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> String {
+                id.0
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = CommErr;
+
+            fn try_from(value: String) -> Result<Self, CommErr> {
+                value.parse()
+            }
+        }
+    };
+}
+
+notion_id!(PageId);
+notion_id!(DatabaseId);
+notion_id!(BlockId);
+notion_id!(UserId);
+
+/**
+ * 页面链接中路径的最后一段即页面ID，数据库视图链接（?v=）的路径为数据库ID，?p=为在数据库中打开的页面
+ */
+impl FromStr for PageId {
+    type Err = CommErr;
+
+    fn from_str(s: &str) -> Result<Self, CommErr> {
+        let id = match query(s, "p") {
+            Some(peek) => peek,
+            None => path_id(s),
+        };
+        uuid(id).map(PageId).ok_or_else(|| invalid(s))
+    }
+}
+
+impl FromStr for DatabaseId {
+    type Err = CommErr;
+
+    fn from_str(s: &str) -> Result<Self, CommErr> {
+        uuid(path_id(s)).map(DatabaseId).ok_or_else(|| invalid(s))
+    }
+}
+
+/**
+ * 块链接的锚点（#）为块ID，没有锚点时页面本身也是块
+ */
+impl FromStr for BlockId {
+    type Err = CommErr;
+
+    fn from_str(s: &str) -> Result<Self, CommErr> {
+        let id = match s.split_once('#') {
+            Some((_, anchor)) if is_url(s) && !anchor.is_empty() => anchor,
+            _ => path_id(s),
+        };
+        uuid(id).map(BlockId).ok_or_else(|| invalid(s))
+    }
+}
+
+/**
+ * 用户没有分享链接，只接受UUID
+ */
+impl FromStr for UserId {
+    type Err = CommErr;
+
+    fn from_str(s: &str) -> Result<Self, CommErr> {
+        uuid(s.trim()).map(UserId).ok_or_else(|| invalid(s))
+    }
+}
+
+impl PageId {
+    /**
+     * 页面中的链接（站内的/id或notion.so、notion.site上的网址）所指向的页面，其他链接返回None
+     */
+    pub fn from_href(href: &str) -> Option<PageId> {
+        if is_url(href) {
+            return href.parse().ok();
+        }
+
+        let path = href.split(['?', '#']).next()?;
+        if !path.starts_with('/') {
+            return None;
+        }
+        let segment = path.trim_end_matches('/').rsplit('/').next()?;
+        segment.rsplit_once('-').and_then(|(_, id)| uuid(id)).or_else(|| uuid(segment)).map(PageId)
+    }
+
+    /**
+     * 页面在Notion上的链接
+     */
+    pub fn url(&self) -> String {
+        "https://www.notion.so/".to_string() + &self.simple()
+    }
+}
+
+impl DatabaseId {
+    pub fn url(&self) -> String {
+        "https://www.notion.so/".to_string() + &self.simple()
+    }
+}
+
+/**
+ * 页面和数据库本身也是块，子页面块和子数据库块的ID就是页面和数据库的ID
+ */
+impl From<PageId> for BlockId {
+    fn from(id: PageId) -> Self {
+        BlockId(id.0)
+    }
+}
+
+impl From<DatabaseId> for BlockId {
+    fn from(id: DatabaseId) -> Self {
+        BlockId(id.0)
+    }
+}
+
+impl From<BlockId> for PageId {
+    fn from(id: BlockId) -> Self {
+        PageId(id.0)
+    }
+}
+
+impl From<BlockId> for DatabaseId {
+    fn from(id: BlockId) -> Self {
+        DatabaseId(id.0)
+    }
+}
+
+fn invalid(s: &str) -> CommErr {
+    CommErr::IdErr(s.to_string())
+}
+
+fn is_url(s: &str) -> bool {
+    s.contains("notion.so/") || s.contains("notion.site/")
+}
+
+/**
+ * 链接路径的最后一段，标题和ID以连字符相连时取末尾的32位；不是链接时原样返回
+ */
+fn path_id(s: &str) -> &str {
+    let s = s.trim();
+    if !is_url(s) {
+        return s;
+    }
+
+    let segment = s.split(['?', '#']).next().unwrap_or_default().trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    match segment.rsplit_once('-') {
+        Some((_, id)) if id.len() == 32 => id,
+        _ => segment,
+    }
+}
+
+/**
+ * 链接查询参数中的值
+ */
+fn query<'a>(s: &'a str, key: &str) -> Option<&'a str> {
+    if !is_url(s) {
+        return None;
+    }
+
+    s.split('#').next()?.split_once('?')?.1.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, v)| *k == key && !v.is_empty())
+        .map(|(_, v)| v)
+}
+
+/**
+ * 校验32位十六进制（可带标准位置的连字符），输出带连字符的小写形式
+ */
+fn uuid(s: &str) -> Option<String> {
+    let hex: String = match s.len() {
+        32 => s.to_string(),
+        36 if [8, 13, 18, 23].iter().all(|&i| s.as_bytes()[i] == b'-') => s.replace('-', ""),
+        _ => return None,
+    };
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let hex = hex.to_lowercase();
+    Some(format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "59833787-2cf9-4fdf-8782-e53db20768a5";
+
+    #[test]
+    fn uuids() {
+        assert_eq!(ID.parse::<PageId>().unwrap().as_str(), ID);
+        assert_eq!("598337872cf94fdf8782e53db20768a5".parse::<PageId>().unwrap().as_str(), ID);
+        assert_eq!("598337872CF94FDF8782E53DB20768A5".parse::<BlockId>().unwrap().as_str(), ID);
+        assert_eq!(format!(" {} ", ID).parse::<UserId>().unwrap().as_str(), ID);
+        assert_eq!(ID.parse::<PageId>().unwrap().simple(), "598337872cf94fdf8782e53db20768a5");
+    }
+
+    #[test]
+    fn share_urls() {
+        let page: PageId = "https://www.notion.so/workspace/Tuscan-kale-598337872cf94fdf8782e53db20768a5".parse().unwrap();
+        assert_eq!(page.as_str(), ID);
+        let site: PageId = "https://team.notion.site/Tuscan-kale-598337872cf94fdf8782e53db20768a5?pvs=4".parse().unwrap();
+        assert_eq!(site, page);
+        assert_eq!(page.url(), "https://www.notion.so/598337872cf94fdf8782e53db20768a5");
+    }
+
+    #[test]
+    fn database_views() {
+        let url = "https://www.notion.so/workspace/a8aec43384f447ed84390e8e42c2e089?v=aa0e3fd2e7a84b3c9a6a9d2d1c4fbbcc&p=598337872cf94fdf8782e53db20768a5&pm=s";
+        assert_eq!(url.parse::<DatabaseId>().unwrap().as_str(), "a8aec433-84f4-47ed-8439-0e8e42c2e089");
+        assert_eq!(url.parse::<PageId>().unwrap().as_str(), ID);
+        let view = "https://www.notion.so/a8aec43384f447ed84390e8e42c2e089?v=aa0e3fd2e7a84b3c9a6a9d2d1c4fbbcc";
+        assert_eq!(view.parse::<PageId>().unwrap().as_str(), "a8aec433-84f4-47ed-8439-0e8e42c2e089");
+    }
+
+    #[test]
+    fn block_anchors() {
+        let url = "https://www.notion.so/Tuscan-kale-a8aec43384f447ed84390e8e42c2e089#598337872cf94fdf8782e53db20768a5";
+        assert_eq!(url.parse::<BlockId>().unwrap().as_str(), ID);
+        assert_eq!(url.parse::<PageId>().unwrap().as_str(), "a8aec433-84f4-47ed-8439-0e8e42c2e089");
+        assert_eq!("https://www.notion.so/Tuscan-kale-598337872cf94fdf8782e53db20768a5#".parse::<BlockId>().unwrap().as_str(), ID);
+    }
+
+    #[test]
+    fn malformed() {
+        for s in ["", "not-an-id", "598337872cf94fdf8782e53db20768a", "598337872cf94fdf8782e53db20768a5f", "598337872cf94fdf8782e53db20768ag",
+            "5983378-72cf9-4fdf-8782-e53db20768a5", "https://example.com/598337872cf94fdf8782e53db20768a5", "https://www.notion.so/"] {
+            assert!(s.parse::<PageId>().is_err(), "{}", s);
+        }
+        assert!(matches!("bad".parse::<DatabaseId>(), Err(CommErr::IdErr(s)) if s == "bad"));
+        assert!(serde_json::from_str::<BlockId>("\"bad\"").is_err());
+        assert_eq!(serde_json::from_str::<BlockId>(&format!("\"{}\"", ID)).unwrap().as_str(), ID);
+    }
+
+    #[test]
+    fn hrefs() {
+        assert_eq!(PageId::from_href("/598337872cf94fdf8782e53db20768a5").unwrap().as_str(), ID);
+        assert_eq!(PageId::from_href("/Tuscan-kale-598337872cf94fdf8782e53db20768a5#heading").unwrap().as_str(), ID);
+        assert_eq!(PageId::from_href(&format!("/{}", ID)).unwrap().as_str(), ID);
+        assert_eq!(PageId::from_href("https://www.notion.so/598337872cf94fdf8782e53db20768a5").unwrap().as_str(), ID);
+        assert!(PageId::from_href("https://example.com/598337872cf94fdf8782e53db20768a5").is_none());
+        assert!(PageId::from_href("/about").is_none());
+        assert!(PageId::from_href("notes.md").is_none());
+    }
+}
@@ -0,0 +1,685 @@
+use serde_json::json;
+
+use super::{block::{BlockElement, FragmentText}, text::*};
+
+
+// Notion代码块支持的语言
+const CODE_LANGUAGE: [&str; 73] = [
+    "abap", "arduino", "bash", "basic", "c", "clojure", "coffeescript", "c++", "c#", "css",
+    "dart", "diff", "docker", "elixir", "elm", "erlang", "flow", "fortran", "f#", "gherkin",
+    "glsl", "go", "graphql", "groovy", "haskell", "html", "java", "javascript", "json", "julia",
+    "kotlin", "latex", "less", "lisp", "livescript", "lua", "makefile", "markdown", "markup", "matlab",
+    "mermaid", "nix", "objective-c", "ocaml", "pascal", "perl", "php", "plain text", "powershell", "prolog",
+    "protobuf", "python", "r", "reason", "ruby", "rust", "sass", "scala", "scheme", "scss",
+    "shell", "sql", "swift", "typescript", "vb.net", "verilog", "vhdl", "visual basic", "webassembly", "xml",
+    "yaml", "java/c/c++/c#", "ebnf",
+];
+
+// 制表符对应的缩进宽度
+const TAB_WIDTH: usize = 4;
+
+
+/**
+ * 将Markdown文本解析为块列表
+ */
+pub fn parse(text: &str) -> Vec<BlockElement> {
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    parse_block(&lines)
+}
+
+fn parse_block(lines: &[String]) -> Vec<BlockElement> {
+    let mut list = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].as_str();
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            i += 1;
+            continue;
+        }
+
+        let base = indent(line);
+        if let Some(fence) = fence(trimmed) {
+            // 代码块
+            let language = code_language(trimmed.trim_start_matches(fence.chars().next().unwrap()));
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && !is_fence_end(lines[i].trim(), &fence) {
+                code.push(strip_indent(&lines[i], base));
+                i += 1;
+            }
+            i += 1;
+
+            let mut be = BlockElement::from_text(BlockType::Code, code.join("\n"));
+            be.status = json!(language);
+            list.push(be);
+        } else if let Some(expr) = trimmed.strip_prefix("$$") {
+            // 公式块
+            let mut expression = Vec::new();
+            match expr.strip_suffix("$$") {
+                Some(expr) => expression.push(expr.to_string()),
+                None => {
+                    if !expr.trim().is_empty() {
+                        expression.push(expr.to_string());
+                    }
+                    i += 1;
+                    while i < lines.len() {
+                        let l = lines[i].trim();
+                        if let Some(end) = l.strip_suffix("$$") {
+                            if !end.trim().is_empty() {
+                                expression.push(end.to_string());
+                            }
+                            break;
+                        }
+                        expression.push(l.to_string());
+                        i += 1;
+                    }
+                },
+            }
+            i += 1;
+            list.push(BlockElement::from_text(BlockType::Equation, expression.join("\n").trim().to_string()));
+        } else if let Some((line_type, text)) = heading(trimmed) {
+            let mut be = BlockElement::from_line(line_type, parse_inline(text));
+            be.status = json!(false);
+            list.push(be);
+            i += 1;
+        } else if is_divider(trimmed) {
+            list.push(BlockElement::from_type(BlockType::Divider));
+            i += 1;
+        } else if is_table(lines, i) {
+            let width = table_cells(trimmed).len();
+            let mut table = BlockElement::from_type(BlockType::Table);
+            table.status = json!({ "table_width": width, "has_column_header": true, "has_row_header": false });
+
+            let mut rows = vec![trimmed];
+            i += 2;
+            while i < lines.len() && lines[i].contains('|') && !lines[i].trim().is_empty() {
+                rows.push(lines[i].trim());
+                i += 1;
+            }
+            for row in rows.into_iter() {
+                let mut cells: Vec<Vec<FragmentText>> = table_cells(row).iter().map(|c| parse_inline(c)).collect();
+                cells.resize_with(width, Vec::new);
+
+                let mut be = BlockElement::from_type(BlockType::TableRow);
+                be.cells = cells;
+                table.child.push(be);
+            }
+            list.push(table);
+        } else if trimmed.starts_with('>') {
+            // 引用块，支持惰性续行
+            let mut quote = Vec::new();
+            while i < lines.len() {
+                let l = lines[i].trim_start();
+                match l.strip_prefix('>') {
+                    Some(l) => quote.push(l.strip_prefix(' ').unwrap_or(l).to_string()),
+                    None if !l.is_empty() && !quote.last().unwrap().trim().is_empty() && !starts_block(lines, i) => quote.push(l.to_string()),
+                    None => break,
+                }
+                i += 1;
+            }
+            list.push(nest(BlockType::Quote, parse_block(&quote)));
+        } else if let Some((line_type, content, text)) = list_marker(line) {
+            // 列表项，缩进大于标记的行都属于该项
+            let mut item = vec![text.to_string()];
+            i += 1;
+            while i < lines.len() {
+                if lines[i].trim().is_empty() {
+                    match (i..lines.len()).find(|&k| !lines[k].trim().is_empty()) {
+                        Some(k) if indent(&lines[k]) > base => {
+                            item.extend((i..k).map(|_| String::default()));
+                            i = k;
+                        },
+                        _ => break,
+                    }
+                } else if indent(&lines[i]) > base {
+                    item.push(strip_indent(&lines[i], content));
+                    i += 1;
+                } else if !item.last().unwrap().trim().is_empty() && !starts_block(lines, i) {
+                    item.push(lines[i].trim().to_string());
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+
+            let (line_type, checked) = match line_type {
+                BlockType::BulletedListItem => match task(&item[0]) {
+                    Some((checked, text)) => {
+                        item[0] = text.to_string();
+                        (BlockType::ToDo, Some(checked))
+                    },
+                    None => (line_type, None),
+                },
+                _ => (line_type, None),
+            };
+
+            let mut be = nest(line_type, parse_block(&item));
+            if let Some(checked) = checked {
+                be.status = json!(checked);
+            }
+            list.push(be);
+        } else {
+            // 段落
+            let mut paragraph = vec![trimmed.to_string()];
+            i += 1;
+            while i < lines.len() && !lines[i].trim().is_empty() && !starts_block(lines, i) {
+                paragraph.push(lines[i].trim().to_string());
+                i += 1;
+            }
+            list.push(BlockElement::from_line(BlockType::Paragraph, parse_inline(&paragraph.join("\n"))));
+        }
+    }
+
+    list
+}
+
+/**
+ * 以首个段落作为块的文本，其余内容作为子块
+ */
+fn nest(line_type: BlockType, mut child: Vec<BlockElement>) -> BlockElement {
+    let line = match child.first() {
+        Some(BlockElement { line_type: BlockType::Paragraph, .. }) => child.remove(0).line,
+        _ => Vec::new(),
+    };
+
+    let mut be = BlockElement::from_line(line_type, line);
+    be.child = child;
+    be
+}
+
+/**
+ * 判断某行是否会打断段落而开始新的块
+ */
+fn starts_block(lines: &[String], i: usize) -> bool {
+    let line = lines[i].trim();
+    fence(line).is_some()
+        || line.starts_with("$$")
+        || line.starts_with('>')
+        || heading(line).is_some()
+        || is_divider(line)
+        || list_marker(&lines[i]).is_some()
+        || is_table(lines, i)
+}
+
+fn fence(line: &str) -> Option<String> {
+    ["```", "~~~"].iter()
+        .find(|f| line.starts_with(*f))
+        .map(|f| line.chars().take_while(|c| *c == f.chars().next().unwrap()).collect())
+}
+
+fn is_fence_end(line: &str, fence: &str) -> bool {
+    line.starts_with(fence) && line.chars().all(|c| fence.starts_with(c))
+}
+
+fn code_language(info: &str) -> String {
+    let info = info.split_whitespace().next().unwrap_or_default().to_lowercase();
+    let language = match info.as_str() {
+        "" | "text" | "txt" | "plain" | "plaintext" => "plain text",
+        "rs" => "rust",
+        "js" | "jsx" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "py" => "python",
+        "rb" => "ruby",
+        "sh" | "zsh" | "console" | "shell-session" => "shell",
+        "cpp" | "cc" | "cxx" => "c++",
+        "cs" | "csharp" => "c#",
+        "fsharp" => "f#",
+        "golang" => "go",
+        "kt" => "kotlin",
+        "yml" => "yaml",
+        "md" => "markdown",
+        "dockerfile" => "docker",
+        "objc" => "objective-c",
+        "tex" => "latex",
+        "ps1" | "pwsh" => "powershell",
+        "make" => "makefile",
+        "proto" => "protobuf",
+        "vb" => "visual basic",
+        "wasm" => "webassembly",
+        lang => lang,
+    };
+
+    if CODE_LANGUAGE.contains(&language) { language } else { "plain text" }.to_string()
+}
+
+fn heading(line: &str) -> Option<(BlockType, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = &line[level..];
+    if level == 0 || level > 6 || !(text.is_empty() || text.starts_with(' ')) {
+        return None;
+    }
+
+    let text = text.trim().trim_end_matches('#').trim_end();
+    let line_type = match level {
+        1 => BlockType::Heading1,
+        2 => BlockType::Heading2,
+        _ => BlockType::Heading3,
+    };
+    Some((line_type, text))
+}
+
+fn is_divider(line: &str) -> bool {
+    let line: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    line.len() >= 3 && ["-", "*", "_"].iter().any(|c| line.chars().all(|l| l.to_string() == *c))
+}
+
+/**
+ * 解析列表标记，返回列表类型、内容起始列和剩余文本
+ */
+fn list_marker(line: &str) -> Option<(BlockType, usize, &str)> {
+    let rest = line.trim_start();
+    let digit = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (line_type, marker) = if rest.starts_with(['-', '*', '+']) {
+        (BlockType::BulletedListItem, 1)
+    } else if (1..10).contains(&digit) && rest[digit..].starts_with(['.', ')']) {
+        (BlockType::NumberedListItem, digit + 1)
+    } else {
+        return None;
+    };
+
+    let text = &rest[marker..];
+    if !text.is_empty() && !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    let space = text.chars().take_while(|c| *c == ' ').count();
+    let space = if (1..=4).contains(&space) { space } else { 1 };
+
+    Some((line_type, indent(line) + marker + space, text.trim_start()))
+}
+
+fn task(text: &str) -> Option<(bool, &str)> {
+    for (mark, checked) in [("[ ]", false), ("[x]", true), ("[X]", true)] {
+        if let Some(text) = text.strip_prefix(mark) {
+            if text.is_empty() || text.starts_with(' ') {
+                return Some((checked, text.trim_start()));
+            }
+        }
+    }
+    None
+}
+
+fn is_table(lines: &[String], i: usize) -> bool {
+    if !lines[i].contains('|') || i + 1 >= lines.len() {
+        return false;
+    }
+
+    let delimiter = table_cells(lines[i + 1].trim());
+    lines[i + 1].contains('-')
+        && delimiter.len() == table_cells(lines[i].trim()).len()
+        && delimiter.iter().all(|c| {
+            let c = c.trim();
+            !c.is_empty() && c.trim_matches(':').chars().all(|c| c == '-')
+        })
+}
+
+/**
+ * 拆分表格行中的单元格，`\|`视为普通字符
+ */
+fn table_cells(row: &str) -> Vec<String> {
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = if row.ends_with('|') && !row.ends_with("\\|") { &row[..row.len() - 1] } else { row };
+
+    let mut cells = vec![String::default()];
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => cells.last_mut().unwrap().push(chars.next().unwrap()),
+            '|' => cells.push(String::default()),
+            _ => cells.last_mut().unwrap().push(c),
+        }
+    }
+
+    cells.into_iter().map(|c| c.trim().to_string()).collect()
+}
+
+fn indent(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += TAB_WIDTH - width % TAB_WIDTH,
+            _ => break,
+        }
+    }
+    width
+}
+
+/**
+ * 去除行首最多`width`列的缩进
+ */
+fn strip_indent(line: &str, width: usize) -> String {
+    let mut col = 0;
+    for (i, c) in line.char_indices() {
+        if col >= width {
+            return line[i..].to_string();
+        }
+        match c {
+            ' ' => col += 1,
+            '\t' => {
+                col += TAB_WIDTH - col % TAB_WIDTH;
+                if col > width {
+                    return " ".repeat(col - width) + &line[i + 1..];
+                }
+            },
+            _ => return line[i..].to_string(),
+        }
+    }
+    String::default()
+}
+
+
+/**
+ * 将行内Markdown解析为rich text片段
+ */
+pub fn parse_inline(text: &str) -> Vec<FragmentText> {
+    let mut line = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    inline(&chars, &[], "", &mut line);
+    line
+}
+
+fn inline(chars: &[char], anno: &[Annotation], href: &str, line: &mut Vec<FragmentText>) {
+    let mut plain = String::default();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if i + 1 < chars.len() && chars[i + 1].is_ascii_punctuation() => {
+                plain.push(chars[i + 1]);
+                i += 2;
+            },
+            '`' => {
+                let n = run(chars, i, c);
+                match find_code_end(chars, i + n, n) {
+                    Some(end) => {
+                        push(line, &mut plain, anno, href);
+                        let mut code: String = chars[i + n..end].iter().collect();
+                        if code.len() > 2 && code.starts_with(' ') && code.ends_with(' ') && !code.trim().is_empty() {
+                            code = code[1..code.len() - 1].to_string();
+                        }
+                        push(line, &mut code, &with(anno, Annotation::Code), href);
+                        i = end + n;
+                    },
+                    None => {
+                        plain.extend(&chars[i..i + n]);
+                        i += n;
+                    },
+                }
+            },
+            '*' | '_' | '~' => {
+                let n = run(chars, i, c).min(2);
+                let annotation = match (c, n) {
+                    ('~', 2) => Some(Annotation::Strikethrough),
+                    ('~', _) => None,
+                    (_, 2) => Some(Annotation::Bold),
+                    _ => Some(Annotation::Italic),
+                };
+                let open = i + n < chars.len()
+                    && !chars[i + n].is_whitespace()
+                    && !(c == '_' && i > 0 && chars[i - 1].is_alphanumeric());
+
+                match (annotation, open) {
+                    (Some(annotation), true) => match find_emphasis_end(chars, i + n, c, n) {
+                        Some(end) => {
+                            push(line, &mut plain, anno, href);
+                            inline(&chars[i + n..end], &with(anno, annotation), href, line);
+                            i = end + n;
+                        },
+                        None => {
+                            plain.extend(&chars[i..i + n]);
+                            i += n;
+                        },
+                    },
+                    _ => {
+                        plain.extend(&chars[i..i + n]);
+                        i += n;
+                    },
+                }
+            },
+            '[' | '!' if chars[i..].starts_with(&['[']) || chars[i..].starts_with(&['!', '[']) => {
+                let start = if c == '!' { i + 1 } else { i };
+                match find_link(chars, start) {
+                    Some((label_end, url, end)) => {
+                        push(line, &mut plain, anno, href);
+                        inline(&chars[start + 1..label_end], anno, &url, line);
+                        i = end;
+                    },
+                    None => {
+                        plain.push(c);
+                        i += 1;
+                    },
+                }
+            },
+            '<' => {
+                // 自动链接 <https://...>
+                let end = chars[i..].iter().position(|c| *c == '>').map(|p| i + p);
+                let url: String = match end {
+                    Some(end) => chars[i + 1..end].iter().collect(),
+                    None => String::default(),
+                };
+                if url.contains("://") && !url.contains(char::is_whitespace) {
+                    push(line, &mut plain, anno, href);
+                    push(line, &mut url.clone(), anno, &url);
+                    i = end.unwrap() + 1;
+                } else {
+                    plain.push(c);
+                    i += 1;
+                }
+            },
+            _ => {
+                plain.push(c);
+                i += 1;
+            },
+        }
+    }
+
+    push(line, &mut plain, anno, href);
+}
+
+fn push(line: &mut Vec<FragmentText>, text: &mut String, anno: &[Annotation], href: &str) {
+    if text.is_empty() {
+        return;
+    }
+
+    let mut annotation = anno.to_vec();
+    Annotation::sort(&mut annotation);
+    line.push(FragmentText { text: std::mem::take(text), href: href.to_string(), annotation, inline: Inline::Text });
+}
+
+fn with(anno: &[Annotation], annotation: Annotation) -> Vec<Annotation> {
+    let mut list = anno.to_vec();
+    if !list.contains(&annotation) {
+        list.push(annotation);
+    }
+    list
+}
+
+fn run(chars: &[char], i: usize, c: char) -> usize {
+    chars[i..].iter().take_while(|x| **x == c).count()
+}
+
+fn find_code_end(chars: &[char], start: usize, n: usize) -> Option<usize> {
+    let mut i = start;
+    while i < chars.len() {
+        let len = run(chars, i, '`');
+        if len == n {
+            return Some(i);
+        }
+        i += len.max(1);
+    }
+    None
+}
+
+/**
+ * 查找强调标记的闭合位置，跳过内部嵌套的另一种强调标记
+ */
+fn find_emphasis_end(chars: &[char], start: usize, c: char, n: usize) -> Option<usize> {
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '`' => {
+                let len = run(chars, i, '`');
+                i = find_code_end(chars, i + len, len).map(|end| end + len).unwrap_or(i + len);
+            },
+            x if x == c => {
+                let len = run(chars, i, c);
+                let end = i + len;
+                let intraword = c == '_' && end < chars.len() && chars[end].is_alphanumeric();
+                let close = !chars[i - 1].is_whitespace() && !intraword;
+                if close && i > start && (len == n || len > 2) {
+                    return Some(end - n);
+                }
+                i = end;
+            },
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/**
+ * 查找链接`[label](url)`，返回标签结束位置、链接地址和整个链接的结束位置
+ */
+fn find_link(chars: &[char], start: usize) -> Option<(usize, String, usize)> {
+    let mut depth = 0;
+    let mut i = start;
+    let label_end = loop {
+        match chars.get(i)? {
+            '\\' => i += 1,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break i;
+                }
+            },
+            _ => (),
+        }
+        i += 1;
+    };
+
+    if chars.get(label_end + 1) != Some(&'(') {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut i = label_end + 1;
+    let end = loop {
+        match chars.get(i)? {
+            '\\' => i += 1,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break i;
+                }
+            },
+            _ => (),
+        }
+        i += 1;
+    };
+
+    let dest: String = chars[label_end + 2..end].iter().collect();
+    let url = dest.split_whitespace().next().unwrap_or_default()
+        .trim_start_matches('<').trim_end_matches('>')
+        .to_string();
+
+    Some((label_end, url, end + 1))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::render::{Gfm, Renderer};
+
+    fn roundtrip(markdown: &str) {
+        assert_eq!(Gfm::default().document(&parse(markdown)), markdown);
+    }
+
+    #[test]
+    fn headings() {
+        roundtrip("# One\n\n## Two\n\n### Three");
+
+        let list = parse("#### Four\n\n#no heading");
+        assert_eq!(list[0].line_type, BlockType::Heading3);
+        assert_eq!(list[1].line_type, BlockType::Paragraph);
+    }
+
+    #[test]
+    fn lists() {
+        roundtrip("- one\n- two\n  - nested\n- three");
+        roundtrip("1. first\n2. second\n   1. inner");
+        roundtrip("- [ ] todo\n- [x] done");
+
+        let list = parse("* a\n  continued\n\n  second paragraph");
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].child[0].line_type, BlockType::Paragraph);
+    }
+
+    #[test]
+    fn code() {
+        roundtrip("```rust\nfn main() {\n    println!(\"{}\", 1);\n}\n```");
+
+        let list = parse("~~~rs\nlet a = 1;\n~~~\n\n```unknown\nx\n```");
+        assert_eq!(list[0].status, json!("rust"));
+        assert_eq!(list[1].status, json!("plain text"));
+        assert_eq!(list[1].line[0].text, "x");
+    }
+
+    #[test]
+    fn tables() {
+        roundtrip("| a | b |\n| --- | --- |\n| 1 | 2 |\n| x \\| y |  |");
+
+        let list = parse("| a | b |\n|---|---|\n| 1 |");
+        assert_eq!(list[0].status["table_width"], json!(2));
+        assert_eq!(list[0].child[1].cells.len(), 2);
+    }
+
+    #[test]
+    fn table_rows_over_limit() {
+        let rows: Vec<String> = (0..150).map(|i| format!("| {} |", i)).collect();
+        let list = parse(&("| n |\n| --- |\n".to_string() + &rows.join("\n")));
+        assert_eq!(list[0].child.len(), 151);
+
+        let json = list[0].to_json();
+        assert_eq!(json["table"]["children"].as_array().unwrap().len(), 100);
+    }
+
+    #[test]
+    fn long_paragraph() {
+        let text = "a".repeat(4500);
+        roundtrip(&text);
+
+        let json = parse(&text)[0].to_json();
+        let chunks = json["paragraph"]["rich_text"].as_array().unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0]["text"]["content"].as_str().unwrap().len(), 2000);
+    }
+
+    #[test]
+    fn long_paragraph_utf16() {
+        // 每个emoji占两个UTF-16编码单元
+        let text = "😀".repeat(1500);
+        let json = parse(&text)[0].to_json();
+        let chunks = json["paragraph"]["rich_text"].as_array().unwrap();
+        assert_eq!(chunks.len(), 2);
+        for chunk in chunks {
+            assert!(chunk["text"]["content"].as_str().unwrap().encode_utf16().count() <= 2000);
+        }
+        assert_eq!(chunks.iter().map(|c| c["text"]["content"].as_str().unwrap()).collect::<String>(), text);
+    }
+
+    #[test]
+    fn inline_styles() {
+        let line = parse_inline("**bold *both*** `code` [link](https://a.b/c) <https://x.y>");
+        assert_eq!(line[0].annotation, vec![Annotation::Bold]);
+        assert_eq!(line[1].annotation, vec![Annotation::Bold, Annotation::Italic]);
+        assert_eq!(line[3].annotation, vec![Annotation::Code]);
+        assert_eq!(line[5].href, "https://a.b/c");
+        assert_eq!(line[7].href, "https://x.y");
+    }
+}
@@ -6,19 +6,37 @@ pub mod property;
 pub mod block;
 pub mod request;
 pub mod text;
+pub mod import;
+pub mod render;
+pub mod asset;
+pub mod tree;
+pub mod model;
+pub mod front_matter;
+pub mod site;
+pub mod vault;
+pub mod id;
 
 
 use self::request::{Request, RequestMethod};
 use sort::{Sort, Direction};
 use filter::Filter;
 use property::PropertyType;
+use block::{Block, BlockElement};
+use text::BlockType;
+use id::{PageId, DatabaseId, BlockId, UserId};
 pub use super::error::CommErr;
 
 use std::fmt::Display;
 pub use serde_json::Value as Json;
 use anyhow::Result;
+use serde_json::json;
 
 
+// 单次追加子块的最大数量
+const APPEND_LIMIT: usize = 100;
+// 分页查询每页的条数
+const PAGE_SIZE: usize = 100;
+
 pub trait NewImp {
     fn new(val: &Json) -> Result<Self>  where Self: Sized;
     // fn search(builder: &NotionBuilder) -> Result<Self>  where Self: Sized;
@@ -27,10 +45,10 @@ pub trait NewImp {
 
 #[allow(dead_code)]
 pub enum Notion {
-    Databases(String),
-    Pages(String),
-    Blocks(String),
-    Users(String),
+    Databases(DatabaseId),
+    Pages(PageId),
+    Blocks(BlockId),
+    Users(UserId),
 }
 
 impl Notion {
@@ -38,10 +56,10 @@ impl Notion {
         {
             use Notion::*;
             match self {
-                Databases(id) => "databases/".to_string() + &id + "/query",
-                Pages(id) => "pages/".to_string() + &id,
-                Blocks(id) => "blocks/".to_string() + &id + "/children",
-                Users(id) => "users/".to_string() + &id,
+                Databases(id) => "databases/".to_string() + id + "/query",
+                Pages(id) => "pages/".to_string() + id,
+                Blocks(id) => "blocks/".to_string() + id + "/children",
+                Users(id) => "users/".to_string() + id,
             }
         }
     }
@@ -62,10 +80,10 @@ impl Notion {
         {
             use Notion::*;
             match self {
-                Databases(s) |
-                Pages(s) |
-                Blocks(s) |
-                Users(s) => s.to_string()
+                Databases(id) => id.to_string(),
+                Pages(id) => id.to_string(),
+                Blocks(id) => id.to_string(),
+                Users(id) => id.to_string(),
             }
         }
     }
@@ -86,6 +104,83 @@ impl Notion {
             .ok_or(CommErr::FormatErr("results"))?
         )
     }
+
+    /**
+     * 获取单个对象（页面、块、数据库或用户）本身的Json
+     */
+    pub fn retrieve(self) -> Result<Json> {
+        let path = {
+            use Notion::*;
+            match self {
+                Databases(id) => "databases/".to_string() + &id,
+                Pages(id) => "pages/".to_string() + &id,
+                Blocks(id) => "blocks/".to_string() + &id,
+                Users(id) => "users/".to_string() + &id,
+            }
+        };
+
+        Request::new()?.query(RequestMethod::GET, path, Json::Null)
+    }
+
+    /**
+     * 将块列表追加为页面或块的子块
+     */
+    pub fn append(self, block: &Block) -> Result<()> {
+        match self {
+            Notion::Pages(id) => append_children(&Request::new()?, id.into(), &block.inner),
+            Notion::Blocks(id) => append_children(&Request::new()?, id, &block.inner),
+            _ => Err(CommErr::CErr("Only pages and blocks can append children").into()),
+        }
+    }
+}
+
+/**
+ * 按数量限制分批追加子块，再逐层追加子块的子块
+ */
+fn append_children(request: &Request, id: BlockId, list: &[BlockElement]) -> Result<()> {
+    append_list(request, id, &BlockElement::creatable(list))
+}
+
+fn append_list(request: &Request, id: BlockId, list: &[&BlockElement]) -> Result<()> {
+    for chunk in list.chunks(APPEND_LIMIT) {
+        let children: Vec<Json> = chunk.iter().map(|be| be.to_json()).collect();
+        let res = request.query(RequestMethod::PATCH, Notion::Blocks(id.clone()).path(), json!({ "children": children }))?;
+        let results = res.get("results")
+            .ok_or(CommErr::FormatErr("results"))?
+            .as_array().ok_or(CommErr::FormatErr("results"))?;
+
+        for (be, created) in chunk.iter().zip(results.iter()) {
+            append_rest(request, get_value_str(created, "id")?.parse()?, be)?;
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * 追加块创建时未能一同创建的子块
+ * 分栏的子块随分栏创建，需获取创建后的ID再追加超出数量限制的子块和子块的子块
+ */
+fn append_rest(request: &Request, id: BlockId, be: &BlockElement) -> Result<()> {
+    match be.line_type {
+        BlockType::Table => append_children(request, id, be.child.get(APPEND_LIMIT..).unwrap_or_default()),
+        BlockType::ColumnList | BlockType::Column => {
+            let list = BlockElement::creatable(&be.child);
+            let (inline, rest) = list.split_at(list.len().min(APPEND_LIMIT));
+            let path = Notion::Blocks(id.clone()).path() + "?page_size=" + &APPEND_LIMIT.to_string();
+            let res = request.query(RequestMethod::GET, path, Json::Null)?;
+            let results = res.get("results")
+                .ok_or(CommErr::FormatErr("results"))?
+                .as_array().ok_or(CommErr::FormatErr("results"))?;
+            for (be, created) in inline.iter().zip(results.iter()) {
+                append_rest(request, get_value_str(created, "id")?.parse()?, be)?;
+            }
+
+            append_list(request, id, rest)
+        },
+        _ if be.child_inline() => Ok(()),
+        _ => append_children(request, id, &be.child),
+    }
 }
 
 
@@ -142,6 +237,68 @@ impl NotionBuilder {
         )
     }
 
+    /**
+     * 按next_cursor依次获取全部结果，limit为最多获取的条数
+     * 每次请求的page_size不超过剩余的条数
+     */
+    pub fn results(&self, limit: Option<usize>) -> Result<Vec<Json>> {
+        let mut list = Vec::new();
+        self.pages(limit, |page| {
+            list.extend(page);
+            Ok(())
+        })?;
+
+        Ok(list)
+    }
+
+    /**
+     * 按next_cursor逐页获取结果，每获取一页就交给f处理，limit为最多获取的条数
+     */
+    pub fn pages(&self, limit: Option<usize>, mut f: impl FnMut(Vec<Json>) -> Result<()>) -> Result<()> {
+        let mut count = 0;
+        let mut cursor: Option<String> = None;
+        loop {
+            let page_size = limit.map_or(PAGE_SIZE, |limit| limit.saturating_sub(count).clamp(1, PAGE_SIZE));
+            let mut path = self.module.path();
+            let mut body = self.format_body();
+            match (self.module.method(), cursor.as_ref()) {
+                (RequestMethod::GET, cursor) => {
+                    path = path + "?page_size=" + &page_size.to_string();
+                    if let Some(cursor) = cursor {
+                        path = path + "&start_cursor=" + cursor;
+                    }
+                },
+                (_, cursor) => {
+                    body["page_size"] = json!(page_size);
+                    if let Some(cursor) = cursor {
+                        body["start_cursor"] = json!(cursor);
+                    }
+                },
+            }
+
+            let mut res = self.request.query(self.module.method(), path, body)?;
+            let mut page = match res.get_mut("results").map(Json::take) {
+                Some(Json::Array(page)) => page,
+                _ => return Err(CommErr::FormatErr("results").into()),
+            };
+            if let Some(limit) = limit {
+                page.truncate(limit - count);
+            }
+            count += page.len();
+            f(page)?;
+            if limit.is_some_and(|limit| count >= limit) {
+                break;
+            }
+
+            match (res.get("has_more").and_then(|m| m.as_bool()), res.get("next_cursor").and_then(|c| c.as_str())) {
+                (Some(true), Some(next)) => cursor = Some(next.to_string()),
+                _ => break,
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn format_body(&self) -> Json {
         serde_json::from_str::<Json>(&self.to_string()).unwrap()
     }
@@ -159,7 +316,7 @@ impl Display for NotionBuilder {
  */
 fn get_property_value<'a>(property: &'a Json, index: Option<&'static str>) -> Result<&'a Json> {
     let property = match index {
-        Some(i) => &property.get(i).ok_or(CommErr::FormatErr(index.unwrap()))?,
+        Some(i) => property.get(i).ok_or(CommErr::FormatErr(index.unwrap()))?,
         None => property,
     };
 
@@ -175,4 +332,4 @@ fn get_value_str(value: &Json, index: &'static str) -> Result<String> {
             .as_str().ok_or(CommErr::GetValueStrErr(index))?
             .to_string()
     )
-}
\ No newline at end of file
+}
@@ -0,0 +1,705 @@
+use serde::{Serialize, Deserialize, Serializer, Deserializer, de::Error as _, ser::Error as _};
+use serde_json::Map;
+use anyhow::Result;
+
+use super::{CommErr, Json, NewImp, block::BlockReader, text::AnnoColor, id::{PageId, DatabaseId, BlockId, UserId}};
+
+
+/**
+ * Notion API返回的完整块对象，保留ID、时间、作者等元数据，子块在获取时一并填充
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockObject {
+    #[serde(default = "block_object")]
+    pub object: String,
+    pub id: BlockId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Tagged<Parent>>,
+    #[serde(default)]
+    pub created_time: String,
+    #[serde(default)]
+    pub last_edited_time: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<PartialUser>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_edited_by: Option<PartialUser>,
+    #[serde(default)]
+    pub has_children: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_trash: Option<bool>,
+    // 块的类型和内容，extra中为块对象上未建模的字段
+    #[serde(flatten)]
+    pub data: Tagged<BlockData>,
+    // 不属于API返回的块对象，由获取时填充
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<BlockObject>,
+}
+
+fn block_object() -> String {
+    "block".to_string()
+}
+
+impl BlockObject {
+    /**
+     * 引用的同步块所指向的原始块ID
+     */
+    pub fn synced_from(&self) -> Option<&BlockId> {
+        match &self.data.value {
+            BlockData::SyncedBlock(synced_block) => synced_block.synced_from.as_ref().map(|from| &from.block_id),
+            _ => None,
+        }
+    }
+
+    /**
+     * 子页面和内嵌数据库的子块是其本身的内容，不随块获取
+     */
+    pub(crate) fn is_page(&self) -> bool {
+        matches!(self.data.value, BlockData::ChildPage(_) | BlockData::ChildDatabase(_))
+    }
+}
+
+impl NewImp for BlockObject {
+    fn new(val: &Json) -> Result<Self> {
+        BlockReader::default().object(val)
+    }
+}
+
+impl NewImp for Vec<BlockObject> {
+    fn new(val: &Json) -> Result<Self> {
+        let mut reader = BlockReader::default();
+        let mut list = Vec::new();
+        for v in val.as_array().ok_or(CommErr::FormatErr("results"))?.iter() {
+            list.push(reader.object(v)?);
+        }
+
+        Ok(list)
+    }
+}
+
+
+/**
+ * 以type字段区分的对象：type的值为类型名，同名字段为该类型的内容
+ * 尚未建模的类型保留类型名和原始内容，其余字段保存在extra中，序列化后与原对象相同
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tagged<T> {
+    pub value: T,
+    pub extra: Map<String, Json>,
+}
+
+/**
+ * 由tagged!生成，按类型名解析和输出内容
+ */
+pub trait TaggedValue: Sized {
+    fn from_parts(tag: &str, content: Option<Json>) -> serde_json::Result<Self>;
+
+    fn tag(&self) -> &str;
+
+    fn content(&self) -> serde_json::Result<Option<Json>>;
+}
+
+impl<'de, T: TaggedValue> Deserialize<'de> for Tagged<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut extra = Map::deserialize(deserializer)?;
+        let tag = match extra.remove("type") {
+            Some(Json::String(tag)) => tag,
+            _ => return Err(D::Error::missing_field("type")),
+        };
+        let content = extra.remove(&tag);
+        let value = T::from_parts(&tag, content).map_err(D::Error::custom)?;
+
+        Ok(Tagged { value, extra })
+    }
+}
+
+impl<T: TaggedValue> Serialize for Tagged<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = Map::new();
+        let tag = self.value.tag().to_string();
+        map.insert("type".to_string(), Json::String(tag.to_string()));
+        if let Some(content) = self.value.content().map_err(S::Error::custom)? {
+            map.insert(tag, content);
+        }
+        map.extend(self.extra.iter().map(|(k, v)| (k.to_string(), v.to_owned())));
+        map.serialize(serializer)
+    }
+}
+
+/**
+ * 生成以type区分的枚举，每个类型名对应一个变体，Unknown保存未建模的类型名和内容
+ */
+macro_rules! tagged {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident($content:ty) = $tag:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum $name {
+            $($variant($content),)*
+            // 类型名和内容，原对象中没有内容字段时为None
+            Unknown(String, Option<Json>),
+        }
+
+        impl TaggedValue for $name {
+            fn from_parts(tag: &str, content: Option<Json>) -> serde_json::Result<Self> {
+                Ok(match tag {
+                    $($tag => $name::$variant(serde_json::from_value(content.unwrap_or_default())?),)*
+                    _ => $name::Unknown(tag.to_string(), content),
+                })
+            }
+
+            fn tag(&self) -> &str {
+                match self {
+                    $($name::$variant(_) => $tag,)*
+                    $name::Unknown(tag, _) => tag,
+                }
+            }
+
+            fn content(&self) -> serde_json::Result<Option<Json>> {
+                match self {
+                    $($name::$variant(content) => serde_json::to_value(content).map(Some),)*
+                    $name::Unknown(_, content) => Ok(content.to_owned()),
+                }
+            }
+        }
+    };
+}
+
+tagged! {
+    /**
+     * 各类型块的内容
+     */
+    BlockData {
+        Paragraph(TextBlock) = "paragraph",
+        Heading1(HeadingBlock) = "heading_1",
+        Heading2(HeadingBlock) = "heading_2",
+        Heading3(HeadingBlock) = "heading_3",
+        BulletedListItem(TextBlock) = "bulleted_list_item",
+        NumberedListItem(TextBlock) = "numbered_list_item",
+        ToDo(ToDoBlock) = "to_do",
+        Toggle(TextBlock) = "toggle",
+        Callout(CalloutBlock) = "callout",
+        Quote(TextBlock) = "quote",
+        Code(CodeBlock) = "code",
+        Divider(EmptyBlock) = "divider",
+        Equation(EquationBlock) = "equation",
+        Template(TemplateBlock) = "template",
+        ChildPage(ChildBlock) = "child_page",
+        ChildDatabase(ChildBlock) = "child_database",
+        Embed(LinkBlock) = "embed",
+        Image(FileBlock) = "image",
+        Video(FileBlock) = "video",
+        File(FileBlock) = "file",
+        Pdf(FileBlock) = "pdf",
+        Audio(FileBlock) = "audio",
+        Bookmark(LinkBlock) = "bookmark",
+        TableOfContents(ColorBlock) = "table_of_contents",
+        Breadcrumb(EmptyBlock) = "breadcrumb",
+        Column(ColumnBlock) = "column",
+        ColumnList(EmptyBlock) = "column_list",
+        LinkPreview(LinkPreviewBlock) = "link_preview",
+        SyncedBlock(SyncedBlock) = "synced_block",
+        LinkToPage(Tagged<LinkToPage>) = "link_to_page",
+        Table(TableBlock) = "table",
+        TableRow(TableRowBlock) = "table_row",
+        Unsupported(EmptyBlock) = "unsupported",
+    }
+}
+
+
+// 以下各结构中的extra保存未建模的字段
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EmptyBlock {
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TextBlock {
+    pub rich_text: Vec<RichText>,
+    #[serde(default)]
+    pub color: AnnoColor,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct HeadingBlock {
+    pub rich_text: Vec<RichText>,
+    #[serde(default)]
+    pub color: AnnoColor,
+    #[serde(default)]
+    pub is_toggleable: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ToDoBlock {
+    pub rich_text: Vec<RichText>,
+    #[serde(default)]
+    pub checked: bool,
+    #[serde(default)]
+    pub color: AnnoColor,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CalloutBlock {
+    pub rich_text: Vec<RichText>,
+    #[serde(default)]
+    pub icon: Option<Tagged<Icon>>,
+    #[serde(default)]
+    pub color: AnnoColor,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CodeBlock {
+    pub rich_text: Vec<RichText>,
+    #[serde(default)]
+    pub caption: Vec<RichText>,
+    #[serde(default)]
+    pub language: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EquationBlock {
+    pub expression: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TemplateBlock {
+    pub rich_text: Vec<RichText>,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+/**
+ * 子页面和内嵌数据库
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ChildBlock {
+    pub title: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+/**
+ * 书签和嵌入
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LinkBlock {
+    pub url: String,
+    #[serde(default)]
+    pub caption: Vec<RichText>,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LinkPreviewBlock {
+    pub url: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+/**
+ * 图片、视频、文件、PDF和音频，source的类型为external或file，其余未建模的字段在source的extra中
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileBlock {
+    #[serde(flatten)]
+    pub source: Tagged<FileSource>,
+    #[serde(default)]
+    pub caption: Vec<RichText>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+tagged! {
+    FileSource {
+        External(ExternalFile) = "external",
+        File(HostedFile) = "file",
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ExternalFile {
+    pub url: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+/**
+ * 上传到Notion的文件，链接在expiry_time后失效
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct HostedFile {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub expiry_time: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+tagged! {
+    Icon {
+        Emoji(String) = "emoji",
+        External(ExternalFile) = "external",
+        File(HostedFile) = "file",
+        CustomEmoji(CustomEmoji) = "custom_emoji",
+    }
+}
+
+/**
+ * 工作区中上传的自定义emoji
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CustomEmoji {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ColorBlock {
+    #[serde(default)]
+    pub color: AnnoColor,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ColumnBlock {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width_ratio: Option<f64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+/**
+ * 同步块，synced_from为空时是原始块
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SyncedBlock {
+    #[serde(default)]
+    pub synced_from: Option<SyncedFrom>,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncedFrom {
+    pub block_id: BlockId,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+tagged! {
+    LinkToPage {
+        PageId(PageId) = "page_id",
+        DatabaseId(DatabaseId) = "database_id",
+        CommentId(String) = "comment_id",
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TableBlock {
+    pub table_width: usize,
+    #[serde(default)]
+    pub has_column_header: bool,
+    #[serde(default)]
+    pub has_row_header: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TableRowBlock {
+    pub cells: Vec<Vec<RichText>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+
+tagged! {
+    /**
+     * 块所在的父对象
+     */
+    Parent {
+        PageId(PageId) = "page_id",
+        DatabaseId(DatabaseId) = "database_id",
+        BlockId(BlockId) = "block_id",
+        Workspace(bool) = "workspace",
+    }
+}
+
+/**
+ * 只含ID的用户对象，有权限时包含名称等信息
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PartialUser {
+    pub id: UserId,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+
+/**
+ * rich text数组中的单个片段，content的extra中为annotations等以外未建模的字段
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RichText {
+    #[serde(flatten)]
+    pub content: Tagged<RichTextContent>,
+    #[serde(default)]
+    pub annotations: Annotations,
+    #[serde(default)]
+    pub plain_text: String,
+    #[serde(default)]
+    pub href: Option<String>,
+}
+
+tagged! {
+    RichTextContent {
+        Text(TextContent) = "text",
+        Mention(Tagged<Mention>) = "mention",
+        Equation(EquationBlock) = "equation",
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TextContent {
+    pub content: String,
+    #[serde(default)]
+    pub link: Option<ExternalFile>,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+tagged! {
+    Mention {
+        User(PartialUser) = "user",
+        Page(Reference<PageId>) = "page",
+        Database(Reference<DatabaseId>) = "database",
+        Date(DateValue) = "date",
+        LinkPreview(LinkPreviewBlock) = "link_preview",
+        LinkMention(LinkMention) = "link_mention",
+        TemplateMention(Json) = "template_mention",
+        CustomEmoji(CustomEmoji) = "custom_emoji",
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reference<I> {
+    pub id: I,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+/**
+ * 粘贴链接后生成的提及，title等预览信息在extra中
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LinkMention {
+    pub href: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct DateValue {
+    pub start: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Annotations {
+    pub bold: bool,
+    pub italic: bool,
+    pub strikethrough: bool,
+    pub underline: bool,
+    pub code: bool,
+    pub color: AnnoColor,
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rich_text(rich_text: Json) -> Json {
+        json!({
+            "object": "block",
+            "id": "c02fc1d3-db8b-45c5-a222-27595b15aea7",
+            "parent": { "type": "page_id", "page_id": "59833787-2cf9-4fdf-8782-e53db20768a5" },
+            "created_time": "2022-03-01T19:05:00.000Z",
+            "last_edited_time": "2022-07-06T19:41:00.000Z",
+            "created_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "last_edited_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "has_children": false,
+            "archived": false,
+            "in_trash": false,
+            "type": "paragraph",
+            "paragraph": { "rich_text": rich_text, "color": "default" },
+        })
+    }
+
+    fn annotations() -> Json {
+        json!({ "bold": false, "italic": false, "strikethrough": false, "underline": false, "code": false, "color": "default" })
+    }
+
+    #[test]
+    fn mentions_roundtrip() {
+        let payload = rich_text(json!([
+            {
+                "type": "mention",
+                "mention": {
+                    "type": "link_mention",
+                    "link_mention": { "href": "https://github.com/", "title": "GitHub", "icon_url": "https://github.com/favicon.ico" },
+                },
+                "annotations": annotations(),
+                "plain_text": "https://github.com/",
+                "href": "https://github.com/",
+            },
+            {
+                "type": "mention",
+                "mention": { "type": "custom_emoji", "custom_emoji": { "id": "45ce454c-d427-4f53-9489-e5d0f3d1db6b", "name": "party", "url": "https://s3.example.com/party.png" } },
+                "annotations": annotations(),
+                "plain_text": ":party:",
+                "href": null,
+            },
+            {
+                "type": "mention",
+                "mention": { "type": "page", "page": { "id": "59833787-2cf9-4fdf-8782-e53db20768a5" } },
+                "annotations": annotations(),
+                "plain_text": "Tuscan kale",
+                "href": "https://www.notion.so/598337872cf94fdf8782e53db20768a5",
+            },
+            {
+                "type": "mention",
+                "mention": { "type": "future_mention", "future_mention": { "anything": [1, 2] } },
+                "annotations": annotations(),
+                "plain_text": "?",
+                "href": null,
+            },
+        ]));
+
+        let object: BlockObject = serde_json::from_value(payload.clone()).unwrap();
+        let BlockData::Paragraph(paragraph) = &object.data.value else { panic!("{:?}", object.data.value) };
+        let mentions: Vec<&str> = paragraph.rich_text.iter().map(|t| match &t.content.value {
+            RichTextContent::Mention(mention) => mention.value.tag(),
+            _ => "",
+        }).collect();
+        assert_eq!(mentions, ["link_mention", "custom_emoji", "page", "future_mention"]);
+        assert_eq!(serde_json::to_value(&object).unwrap(), payload);
+    }
+
+    #[test]
+    fn unknown_fields_roundtrip() {
+        let mut payload = rich_text(json!([{
+            "type": "text",
+            "text": { "content": "Lacinato kale", "link": null },
+            "annotations": annotations(),
+            "plain_text": "Lacinato kale",
+            "href": null,
+        }]));
+        payload["request_id"] = json!("7f1a4e2b");
+        payload["paragraph"]["new_setting"] = json!(true);
+
+        let object: BlockObject = serde_json::from_value(payload.clone()).unwrap();
+        assert_eq!(object.data.extra.get("request_id"), Some(&json!("7f1a4e2b")));
+        assert_eq!(serde_json::to_value(&object).unwrap(), payload);
+    }
+
+    #[test]
+    fn blocks_roundtrip() {
+        let payload = json!([
+            {
+                "object": "block", "id": "9bc30ad4-9373-46a5-84ab-0a7845ee52e6", "created_time": "2022-03-01T19:05:00.000Z",
+                "last_edited_time": "2022-03-01T19:05:00.000Z", "has_children": false, "archived": false,
+                "type": "callout",
+                "callout": {
+                    "rich_text": [],
+                    "icon": { "type": "custom_emoji", "custom_emoji": { "id": "45ce454c-d427-4f53-9489-e5d0f3d1db6b", "name": "party", "url": "https://s3.example.com/party.png" } },
+                    "color": "gray_background",
+                },
+            },
+            {
+                "object": "block", "id": "2b0c7b5e-3b1d-4a7c-9c3e-5a8a1e9f0d11", "created_time": "2022-03-01T19:05:00.000Z",
+                "last_edited_time": "2022-03-01T19:05:00.000Z", "has_children": false, "archived": false,
+                "type": "image",
+                "image": {
+                    "caption": [], "type": "file",
+                    "file": { "url": "https://s3.us-west-2.amazonaws.com/secure.notion-static.com/kale.png?X-Amz-Expires=3600", "expiry_time": "2022-03-01T20:05:00.000Z" },
+                },
+            },
+            {
+                "object": "block", "id": "f4b7a0d2-8e5c-4c3b-a1f9-0e2d3c4b5a69", "created_time": "2022-03-01T19:05:00.000Z",
+                "last_edited_time": "2022-03-01T19:05:00.000Z", "has_children": false, "archived": false,
+                "type": "link_to_page",
+                "link_to_page": { "type": "page_id", "page_id": "59833787-2cf9-4fdf-8782-e53db20768a5" },
+            },
+            {
+                "object": "block", "id": "a1b2c3d4-e5f6-4a7b-8c9d-0e1f2a3b4c5d", "created_time": "2022-03-01T19:05:00.000Z",
+                "last_edited_time": "2022-03-01T19:05:00.000Z", "has_children": false, "archived": false,
+                "type": "transcription",
+                "transcription": { "title": [], "status": "notes_ready" },
+            },
+        ]);
+
+        let list: Vec<BlockObject> = serde_json::from_value(payload.clone()).unwrap();
+        assert!(matches!(&list[0].data.value, BlockData::Callout(CalloutBlock { icon: Some(Tagged { value: Icon::CustomEmoji(_), .. }), .. })));
+        assert!(matches!(&list[1].data.value, BlockData::Image(FileBlock { source: Tagged { value: FileSource::File(_), .. }, .. })));
+        assert!(matches!(&list[3].data.value, BlockData::Unknown(tag, Some(_)) if tag == "transcription"));
+        assert_eq!(serde_json::to_value(&list).unwrap(), payload);
+    }
+
+    #[test]
+    fn optional_fields_roundtrip() {
+        let mut payload = rich_text(json!([{
+            "type": "mention",
+            "mention": { "type": "date", "date": { "start": "2022-03-01" } },
+            "annotations": { "bold": false, "italic": false, "strikethrough": false, "underline": false, "code": false, "color": "teal_background" },
+            "plain_text": "2022-03-01",
+            "href": null,
+        }]));
+        payload["paragraph"]["color"] = json!("teal");
+
+        let object: BlockObject = serde_json::from_value(payload.clone()).unwrap();
+        let BlockData::Paragraph(paragraph) = &object.data.value else { panic!("{:?}", object.data.value) };
+        assert_eq!(paragraph.color, AnnoColor::Other("teal".to_string()));
+        assert_eq!(serde_json::to_value(&object).unwrap(), payload);
+
+        let file = json!({ "url": "https://s3.us-west-2.amazonaws.com/secure.notion-static.com/kale.png" });
+        let hosted: HostedFile = serde_json::from_value(file.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&hosted).unwrap(), file);
+    }
+
+    #[test]
+    fn malformed_known_type() {
+        let mut payload = rich_text(json!([]));
+        payload["paragraph"] = json!({ "rich_text": "not a list" });
+        assert!(serde_json::from_value::<BlockObject>(payload).is_err());
+    }
+}
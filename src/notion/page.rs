@@ -1,17 +1,20 @@
-use super::{Notion, get_property_value, get_value_str, property::Property, property::Author, block::Block, Json, CommErr, NewImp};
+use super::{Notion, id::PageId, get_property_value, database::property_value, get_value_str, property::Property, property::Author, block::{Block, BlockElement, BlockReader, Outline, has_contents}, model::BlockObject, front_matter::FrontMatter, render::{html, Html, Renderer, Stream, IoWriter}, Json, CommErr, NewImp};
 use anyhow::Result;
+use std::{fmt, io};
 
 
 // 页结构
 #[derive(Debug)]
 pub struct Page {
-    pub id: String,
+    pub id: PageId,
     pub created_time: String,
     pub edited_time: String,
     pub author: Author,
     pub editor_id: String,
     pub cover: String,
     pub icon: String,
+    // 封面和图标中由Notion托管（type为file）的项
+    pub hosted: Vec<&'static str>,
     pub title: String,
     pub archived: bool,
     pub url: String,
@@ -19,32 +22,117 @@ pub struct Page {
     pub content: Block,
 }
 
+/**
+ * 页面属性与语义字段的映射
+ * 默认以title类型的属性为标题，Author为作者（没有时使用页面的创建者），Created time和Edited time为创建和编辑时间
+ */
+#[derive(Debug, Clone)]
+pub struct PageSchema {
+    title: Option<String>,
+    author: String,
+    created_time: String,
+    edited_time: String,
+}
+
+impl Default for PageSchema {
+    fn default() -> Self {
+        PageSchema {
+            title: None,
+            author: "Author".to_string(),
+            created_time: "Created time".to_string(),
+            edited_time: "Edited time".to_string(),
+        }
+    }
+}
+
+impl PageSchema {
+    /**
+     * 作为标题的属性，可以是title以外的文本类型
+     */
+    pub fn title(mut self, name: &str) -> Self {
+        self.title = Some(name.to_string());
+        self
+    }
+
+    pub fn author(mut self, name: &str) -> Self {
+        self.author = name.to_string();
+        self
+    }
+
+    pub fn created_time(mut self, name: &str) -> Self {
+        self.created_time = name.to_string();
+        self
+    }
+
+    pub fn edited_time(mut self, name: &str) -> Self {
+        self.edited_time = name.to_string();
+        self
+    }
+}
+
+
 impl NewImp for Page {
     fn new(page: &Json) -> Result<Self> {
-        let property_list = page.get("properties").ok_or(CommErr::FormatErr("properties"))?;
+        Page::with_schema(page, &PageSchema::default())
+    }
+}
 
-        let author = Author::new(property_list)?;
+impl Page {
+    /**
+     * 按属性映射解析页面，映射到语义字段的属性不再出现在properties中
+     */
+    pub fn with_schema(page: &Json, schema: &PageSchema) -> Result<Self> {
+        let property_list = page.get("properties")
+            .and_then(|properties| properties.as_object())
+            .ok_or(CommErr::FormatErr("properties"))?;
+
+        let title_key = match &schema.title {
+            Some(name) => name.to_string(),
+            None => property_list.iter()
+                .find(|(_, p)| p.get("type").and_then(|t| t.as_str()) == Some("title"))
+                .map(|(key, _)| key.to_string())
+                .ok_or(CommErr::FormatErr("title"))?,
+        };
+        let title = get_property_value(property_list.get(&title_key).ok_or(CommErr::FormatErr("title"))?, None)?
+            .as_array().ok_or(CommErr::FormatErr("title"))?
+            .iter()
+            .filter_map(|t| t.get("plain_text").and_then(|t| t.as_str()))
+            .collect();
+
+        // 作者属性可以是创建者或人员类型，没有该属性时使用页面的创建者
+        let author = match property_list.get(&schema.author).map(|p| get_property_value(p, None)) {
+            Some(Ok(Json::Array(people))) if !people.is_empty() => Author::from_user(&people[0]),
+            Some(Ok(user)) if user.is_object() => Author::from_user(user),
+            _ => Author::from_user(&page["created_by"]),
+        };
+
+        // 映射的时间属性有值时优先于页面本身的时间
+        let time = |name: &str, index: &'static str| -> Result<String> {
+            match property_list.get(name).map(property_value) {
+                Some(Json::String(time)) => Ok(time),
+                Some(Json::Object(range)) if range.contains_key("start") => Ok(range["start"].as_str().unwrap_or_default().to_string()),
+                _ => get_value_str(page, index),
+            }
+        };
 
+        let mapped = [title_key.as_str(), schema.author.as_str(), schema.created_time.as_str(), schema.edited_time.as_str()];
         let mut properties: Vec<Property> = Vec::new();
-        for (key, value) in property_list.as_object().ok_or(CommErr::FormatErr("properties"))?.iter() {
-            match key.as_str() {
-                "Author" | "Created time" | "Edited time" | "Name" => (),
-                _ => properties.push(Property::new(key, value)?),
+        for (key, value) in property_list.iter() {
+            if !mapped.contains(&key.as_str()) {
+                properties.push(Property::new(key, value)?);
             }
         }
 
         Ok(Page {
-            id: get_value_str(page, "id")?,
-            created_time: get_value_str(page, "created_time")?,
-            edited_time: get_value_str(page, "last_edited_time")?,
+            id: get_value_str(page, "id")?.parse()?,
+            created_time: time(&schema.created_time, "created_time")?,
+            edited_time: time(&schema.edited_time, "last_edited_time")?,
             author,
             editor_id: get_value_str(&page["last_edited_by"], "id").unwrap_or_default(),
-            cover: get_value_str(page, "cover").unwrap_or_default(),
-            icon: get_value_str(page, "icon").unwrap_or_default(),
-            title: get_value_str(
-                get_property_value(property_list, Some("Name"))?
-                .get(0).ok_or(CommErr::FormatErr("properties"))?
-            , "plain_text")?,
+            cover: file_url(page, "cover"),
+            icon: file_url(page, "icon"),
+            hosted: ["cover", "icon"].into_iter().filter(|index| is_file(page, index)).collect(),
+            title,
             archived: page.get("archived")
                 .ok_or(CommErr::FormatErr("archived"))?
                 .as_bool().unwrap_or_default(),
@@ -53,14 +141,105 @@ impl NewImp for Page {
             content: Block::default(),
         })
     }
-}
 
-impl Page {
     pub fn content(&mut self) -> Result<String> {
-        let block = Notion::Blocks(self.id.to_string()).search::<Block>()?;
-        self.content = block;
+        Ok(self.load()?.to_string())
+    }
+
+    /**
+     * 获取页面内容并缓存，可在输出前对块做进一步处理
+     */
+    pub fn load(&mut self) -> Result<&mut Block> {
+        self.load_with(&mut BlockReader::default())
+    }
+
+    /**
+     * 以指定的解析模式获取页面内容
+     */
+    pub fn load_with(&mut self, reader: &mut BlockReader) -> Result<&mut Block> {
+        self.content = reader.read(self.id.clone().into())?;
+        self.content.outline();
+        Ok(&mut self.content)
+    }
+
+    /**
+     * 获取页面内容的完整块对象，保留块的元数据和全部字段
+     */
+    pub fn blocks(&self) -> Result<Vec<BlockObject>> {
+        BlockReader::default().objects(self.id.clone().into())
+    }
+
+    /**
+     * 获取页面内容的HTML，standalone为true时输出包含默认样式的完整文档
+     */
+    pub fn html(&mut self, standalone: bool) -> Result<String> {
+        let body = self.render(&Html::default())?;
+        Ok(if standalone { html::document(&self.title, &body) } else { body })
+    }
+
+    /**
+     * 使用指定的渲染器输出页面内容
+     */
+    pub fn render<R: Renderer + ?Sized>(&mut self, renderer: &R) -> Result<String> {
+        Ok(self.load()?.render(renderer))
+    }
 
-        Ok(self.content.to_string())
+    /**
+     * 使用指定的渲染器将页面内容写入out，子块每获取一页就写出，不缓存页面内容
+     * 目录块需要全部的标题，第一个目录块及其后的内容会缓存到页面获取完毕后再写出
+     */
+    pub fn write<R: Renderer + ?Sized, W: io::Write + ?Sized>(&self, renderer: &R, out: &mut W) -> Result<()> {
+        self.write_with(renderer, &mut BlockReader::default(), out, |_| Ok(()))
+    }
+
+    /**
+     * 以指定的解析模式获取并写出页面内容，每获取一页块就先交给transform处理（如改写链接、下载文件）再写出
+     */
+    pub fn write_with<R: Renderer + ?Sized, W: io::Write + ?Sized>(&self, renderer: &R, reader: &mut BlockReader, out: &mut W, transform: impl FnMut(&mut Vec<BlockElement>) -> Result<()>) -> Result<()> {
+        let mut sink = IoWriter::new(out);
+        match self.stream(renderer, reader, &mut sink, transform) {
+            Err(e) if e.is::<fmt::Error>() => Err(sink.error().into()),
+            res => res,
+        }
+    }
+
+    /**
+     * 目录块需要全部的标题，遇到目录块后其余的内容在获取完毕后才写出
+     */
+    fn stream<R: Renderer + ?Sized>(&self, renderer: &R, reader: &mut BlockReader, out: &mut dyn fmt::Write, mut transform: impl FnMut(&mut Vec<BlockElement>) -> Result<()>) -> Result<()> {
+        let mut stream = Stream::new(renderer, out);
+        let mut outline = Outline::default();
+        let mut held: Option<Vec<BlockElement>> = None;
+        reader.pages(self.id.clone().into(), |mut list| {
+            transform(&mut list)?;
+            outline.anchors(&mut list);
+            match held.as_mut() {
+                Some(held) => held.append(&mut list),
+                None if has_contents(&list) => held = Some(list),
+                None => stream.push(list)?,
+            }
+            Ok(())
+        })?;
+
+        if let Some(mut list) = held {
+            outline.contents(&mut list);
+            stream.push(list)?;
+        }
+        Ok(stream.finish()?)
+    }
+
+    /**
+     * 按设置生成页面的front matter
+     */
+    pub fn front_matter(&self, matter: &FrontMatter) -> String {
+        matter.render(self)
+    }
+
+    /**
+     * 将Markdown文本解析为块并追加到页面末尾
+     */
+    pub fn import(&self, markdown: &str) -> Result<()> {
+        Notion::Pages(self.id.clone()).append(&Block::from_markdown(markdown))
     }
 
     pub fn search_property(&self, key: &str) -> Result<Vec<(String, String)>> {
@@ -88,3 +267,104 @@ impl Page {
         Ok(res)
     }
 }
+
+/**
+ * 封面和图标的链接，emoji图标直接返回emoji
+ */
+pub fn file_url(page: &Json, index: &'static str) -> String {
+    let file = match page.get(index) {
+        Some(file) if !file.is_null() => get_property_value(file, None),
+        _ => return String::default(),
+    };
+
+    file.ok()
+        .and_then(|f| f.as_str().or_else(|| f.get("url").and_then(|url| url.as_str())))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/**
+ * 封面或图标是否为Notion托管的文件，外部链接的类型为external
+ */
+fn is_file(page: &Json, index: &str) -> bool {
+    page.get(index).and_then(|file| file.get("type")).and_then(|kind| kind.as_str()) == Some("file")
+}
+
+/**
+ * 页面的标题，取类型为title的属性
+ */
+pub fn page_title(page: &Json) -> String {
+    let title = page.get("properties")
+        .and_then(|properties| properties.as_object())
+        .and_then(|properties| properties.values().find(|p| p.get("type").and_then(|t| t.as_str()) == Some("title")))
+        .and_then(|p| p.get("title"))
+        .and_then(|title| title.as_array());
+
+    title.map(|list| list.iter().filter_map(|t| t.get("plain_text").and_then(|t| t.as_str())).collect())
+        .unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use super::super::property::PropertyType;
+
+    fn page(properties: Json) -> Json {
+        json!({
+            "object": "page",
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "created_time": "2023-05-01T09:30:00.000Z",
+            "last_edited_time": "2023-05-02T10:00:00.000Z",
+            "created_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "last_edited_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "archived": false,
+            "properties": properties,
+        })
+    }
+
+    fn properties() -> Json {
+        json!({
+            "Task": { "id": "title", "type": "title", "title": [{ "type": "text", "plain_text": "Write docs" }] },
+            "Summary": { "id": "a", "type": "rich_text", "rich_text": [{ "type": "text", "plain_text": "Short summary" }] },
+            "Created": { "id": "b", "type": "created_time", "created_time": "2023-04-01T08:00:00.000Z" },
+            "Creator": { "id": "c", "type": "created_by", "created_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" } },
+            "Edited": { "id": "d", "type": "last_edited_time", "last_edited_time": "2023-04-02T08:00:00.000Z" },
+            "Link": { "id": "e", "type": "url", "url": "https://example.com" },
+            "Mail": { "id": "f", "type": "email", "email": null },
+            "Phone": { "id": "g", "type": "phone_number", "phone_number": "123" },
+            "Ticket": { "id": "h", "type": "unique_id", "unique_id": { "prefix": "T", "number": 42 } },
+        })
+    }
+
+    #[test]
+    fn title_column_not_named_name() {
+        let page = Page::with_schema(&page(properties()), &PageSchema::default().created_time("Created")).unwrap();
+        assert_eq!(page.title, "Write docs");
+        assert_eq!(page.created_time, "2023-04-01T08:00:00.000Z");
+        assert_eq!(page.edited_time, "2023-05-02T10:00:00.000Z");
+
+        let names: Vec<String> = page.properties.iter().map(|p| p.property.get_val()).collect();
+        assert_eq!(names, ["Summary", "Creator", "Edited", "Link", "Mail", "Phone", "Ticket"]);
+    }
+
+    #[test]
+    fn text_column_as_title() {
+        let page = Page::with_schema(&page(properties()), &PageSchema::default().title("Summary").edited_time("Edited")).unwrap();
+        assert_eq!(page.title, "Short summary");
+        assert_eq!(page.edited_time, "2023-04-02T08:00:00.000Z");
+        assert!(page.properties.iter().any(|p| matches!(p.property, PropertyType::Title("Task"))));
+        assert!(page.properties.iter().any(|p| p.property.get_val() == "Created"));
+    }
+
+    #[test]
+    fn unknown_property_type() {
+        let mut properties = properties();
+        properties["Future"] = json!({ "id": "z", "type": "hologram", "hologram": { "beam": 3 } });
+        let page = Page::new(&page(properties)).unwrap();
+        let future = page.properties.iter().find(|p| p.property == PropertyType::Unknown("Future")).unwrap();
+        assert!(future.data.is_empty());
+        assert_eq!(future.value["hologram"]["beam"], 3);
+    }
+}
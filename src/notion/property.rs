@@ -9,7 +9,7 @@ use anyhow::Result;
 
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Author {
     id: String,
     name: String,
@@ -19,6 +19,10 @@ pub struct Author {
 }
 
 impl Author {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn new(property_list: &Json) -> Result<Self> {
         let author = get_property_value(property_list, Some("Author"))?;
         Ok(Author {
@@ -29,6 +33,20 @@ impl Author {
             user_type: get_value_str(author, "type")?,
         })
     }
+
+    /**
+     * 由用户对象生成，页面的created_by等只含ID的部分用户对象缺少的字段为空
+     */
+    pub fn from_user(user: &Json) -> Self {
+        let field = |value: &Json, index: &'static str| get_value_str(value, index).unwrap_or_default();
+        Author {
+            id: field(user, "id"),
+            name: field(user, "name"),
+            avatar_url: field(user, "avatar_url"),
+            email: field(&user["person"], "email"),
+            user_type: field(user, "type"),
+        }
+    }
 }
 
 
@@ -48,6 +66,20 @@ pub enum PropertyType {
     Relation(&'static str),
     Rollup(&'static str),
     Formula(&'static str),
+    Title(&'static str),
+    Url(&'static str),
+    Email(&'static str),
+    PhoneNumber(&'static str),
+    CreatedTime(&'static str),
+    CreatedBy(&'static str),
+    LastEditedTime(&'static str),
+    LastEditedBy(&'static str),
+    UniqueId(&'static str),
+    Verification(&'static str),
+    Button(&'static str),
+    // 无法识别的新增属性类型，只保留属性的原始Json
+    #[strum(disabled)]
+    Unknown(&'static str),
 }
 
 impl PropertyType {
@@ -79,7 +111,19 @@ impl PropertyType {
                 Files(s) |
                 Relation(s) |
                 Rollup(s) |
-                Formula(s) => s.to_string()
+                Formula(s) |
+                Title(s) |
+                Url(s) |
+                Email(s) |
+                PhoneNumber(s) |
+                CreatedTime(s) |
+                CreatedBy(s) |
+                LastEditedTime(s) |
+                LastEditedBy(s) |
+                UniqueId(s) |
+                Verification(s) |
+                Button(s) |
+                Unknown(s) => s.to_string()
             }
         }
     }
@@ -101,6 +145,18 @@ impl PropertyType {
                 Relation(_) => Relation(val),
                 Rollup(_) => Rollup(val),
                 Formula(_) => Formula(val),
+                Title(_) => Title(val),
+                Url(_) => Url(val),
+                Email(_) => Email(val),
+                PhoneNumber(_) => PhoneNumber(val),
+                CreatedTime(_) => CreatedTime(val),
+                CreatedBy(_) => CreatedBy(val),
+                LastEditedTime(_) => LastEditedTime(val),
+                LastEditedBy(_) => LastEditedBy(val),
+                UniqueId(_) => UniqueId(val),
+                Verification(_) => Verification(val),
+                Button(_) => Button(val),
+                Unknown(_) => Unknown(val),
             }
         }
     }
@@ -112,13 +168,26 @@ impl PropertyType {
 pub struct Property {
     pub property: PropertyType,
     pub data: Vec<HashMap<String, String>>,
+    // 属性的原始Json
+    pub value: Json,
 }
 
 impl Property {
     pub fn new(key: &String, value: &Json) -> Result<Self> {
-        let data = get_property_value(value, None)?;
         let type_name = get_value_str(value, "type")?;
 
+        // 新增的属性类型无法识别时保留原始Json，不影响页面和数据库的加载
+        let property = match PropertyType::from_str(&type_name) {
+            Ok(property) => property.reset_val(key.to_string()),
+            Err(_) => return Ok(Property {
+                property: PropertyType::Unknown("").reset_val(key.to_string()),
+                data: Vec::new(),
+                value: value.to_owned(),
+            }),
+        };
+
+        let data = get_property_value(value, None)?;
+
         let data = if !data.is_array() {
             vec![data.to_owned()] 
         } else { 
@@ -142,11 +211,10 @@ impl Property {
             property_data_opt.push(hm);
         }
 
-        let property = PropertyType::from_str(&type_name).unwrap().reset_val(key.to_string());
-
         Ok(Property {
             property,
             data: property_data_opt,
+            value: value.to_owned(),
         })
     }
 }
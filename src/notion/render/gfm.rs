@@ -0,0 +1,138 @@
+use strum::EnumProperty;
+use std::fmt;
+
+use super::{Renderer, Child, Indent, fill, placeholder, surround, render_item};
+use super::markdown::sections;
+use super::super::{block::BlockElement, text::*};
+
+
+/**
+ * 严格的GitHub Flavored Markdown输出，不含任何HTML标签
+ * 颜色和下划线被舍弃，标注块输出为引用，折叠块输出为列表
+ * 各个块的输出即Renderer的默认实现，这里只处理可选的标记
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Gfm {
+    column_divider: bool,
+    synced_source: bool,
+    placeholder: Option<String>,
+}
+
+impl Gfm {
+    /**
+     * 分栏之间是否输出分隔线
+     */
+    pub fn column_divider(mut self, divider: bool) -> Self {
+        self.column_divider = divider;
+        self
+    }
+
+    /**
+     * 是否标出同步块的来源，标记使用不会显示的空链接定义
+     */
+    pub fn synced_source(mut self, annotate: bool) -> Self {
+        self.synced_source = annotate;
+        self
+    }
+
+    /**
+     * 未解析的块输出为template，{type}替换为块类型，{id}替换为块ID；默认不输出
+     */
+    pub fn placeholder(mut self, template: &str) -> Self {
+        self.placeholder = Some(template.to_string());
+        self
+    }
+}
+
+impl Renderer for Gfm {
+    fn unsupported(&self, be: &BlockElement, block_type: &str) -> String {
+        match &self.placeholder {
+            Some(template) => placeholder(template, be, block_type),
+            None => String::default(),
+        }
+    }
+
+    /**
+     * 同步的内容没有输出时不加标记
+     */
+    fn synced(&self, _be: &BlockElement, source: &str, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        if !self.synced_source {
+            return child(out);
+        }
+
+        surround(out, &format!("[//]: # (synced_block: {})\n\n", source), child, "\n\n[//]: # (/synced_block)")
+    }
+
+    fn columns(&self, _be: &BlockElement, columns: Vec<(f64, Child)>, out: &mut dyn fmt::Write) -> fmt::Result {
+        sections(columns, self.column_divider, out)
+    }
+}
+
+/**
+ * 按块类型的gfm模板填充文本和状态
+ */
+pub(super) fn format(be: &BlockElement, text: &str, status: &str) -> String {
+    fill(&be.line_type.get_str("gfm").unwrap_or("{}").replace("{status}", status), text)
+}
+
+/**
+ * 为每一行添加引用标记
+ */
+pub(super) fn blockquote(out: &mut dyn fmt::Write) -> Indent<'_> {
+    Indent::new(out, "> ").blank(Some(">"))
+}
+
+/**
+ * 子块以空行分隔排在块之后
+ */
+pub(super) fn join(block: &str, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+    out.write_str(block)?;
+    surround(out, if block.is_empty() { "" } else { "\n\n" }, child, "")
+}
+
+/**
+ * 列表项逐行排列
+ */
+pub(super) fn list_items<R: Renderer + ?Sized>(renderer: &R, items: &[BlockElement], out: &mut dyn fmt::Write) -> fmt::Result {
+    for (i, be) in items.iter().enumerate() {
+        if i > 0 {
+            out.write_str("\n")?;
+        }
+        render_item(renderer, be, i + 1, out)?;
+    }
+
+    Ok(())
+}
+
+/**
+ * 块之间以空行分隔，跳过没有输出的块
+ * 两个同类列表之间的块没有输出时，插入不会显示的空链接定义，否则两个列表会被合并为一个
+ */
+pub(super) fn separator(previous: Option<&BlockType>, next: &BlockType) -> &'static str {
+    match previous {
+        None => "",
+        Some(previous) if next.is_list() && previous == next => "\n\n[//]: # (list break)\n\n",
+        Some(_) => "\n\n",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::block::Block;
+
+    #[test]
+    fn lists_separated_by_empty_block() {
+        let mut list = Block::from_markdown("1. n1").inner;
+        list.push(BlockElement::from_type(BlockType::Paragraph));
+        list.extend(Block::from_markdown("1. n after break").inner);
+
+        assert_eq!(Gfm::default().document(&list), "1. n1\n\n[//]: # (list break)\n\n1. n after break");
+    }
+
+    #[test]
+    fn different_lists_not_separated() {
+        let list = Block::from_markdown("1. one\n\n- two").inner;
+        assert_eq!(Gfm::default().document(&list), "1. one\n\n- two");
+    }
+}
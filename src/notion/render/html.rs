@@ -0,0 +1,449 @@
+use strum::EnumProperty;
+use std::fmt;
+
+use super::{Renderer, Child, fill, fill_child, placeholder, icon, render_item, render_line, render_fragment};
+use super::super::{block::{BlockElement, FragmentText, Media}, text::*};
+
+
+// 独立HTML文档的默认样式
+pub const STYLESHEET: &str = r#"
+body { margin: 0; color: #37352f; background: #fff; font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica, "PingFang SC", "Microsoft YaHei", sans-serif; line-height: 1.6; }
+article { max-width: 900px; margin: 0 auto; padding: 48px 24px; }
+h1, h2, h3 { margin: 1.4em 0 0.4em; line-height: 1.3; }
+p { margin: 0.4em 0; min-height: 1em; }
+summary > h1, summary > h2, summary > h3 { display: inline; }
+details { margin: 0.4em 0; }
+summary { cursor: pointer; }
+details > :not(summary), .indented { margin-left: 1.6em; }
+blockquote { margin: 0.6em 0; padding: 0 0.9em; border-left: 3px solid currentColor; }
+aside { display: flex; margin: 0.6em 0; padding: 16px 16px 16px 12px; border-radius: 4px; background: rgba(241, 241, 239, 1); }
+aside .callout-icon { margin-right: 8px; }
+ul.to-do-list { list-style: none; padding-left: 0.2em; }
+ul.to-do-list input { margin-right: 0.5em; }
+ul.to-do-list input:checked + span { text-decoration: line-through; opacity: 0.4; }
+pre { padding: 16px; border-radius: 4px; background: rgba(247, 246, 243, 1); overflow-x: auto; tab-size: 4; }
+code { font-family: "SFMono-Regular", Menlo, Consolas, monospace; font-size: 85%; }
+:not(pre) > code { padding: 0.2em 0.4em; border-radius: 3px; background: rgba(135, 131, 120, 0.15); color: #eb5757; }
+hr { border: none; border-top: 1px solid rgba(55, 53, 47, 0.16); }
+.equation { margin: 0.6em 0; text-align: center; overflow-x: auto; }
+.mention { color: rgba(55, 53, 47, 0.65); }
+table { margin: 0.6em 0; border-collapse: collapse; }
+th, td { padding: 6px 10px; border: 1px solid rgba(55, 53, 47, 0.16); text-align: left; vertical-align: top; }
+th { background: rgba(247, 246, 243, 1); font-weight: 600; }
+figure { margin: 0.8em 0; }
+.column-list { display: flex; gap: 24px; }
+.column { min-width: 0; }
+.database-title { font-weight: 600; }
+nav.table-of-contents ul { margin: 0.2em 0; }
+a.bookmark { display: block; padding: 12px 14px; border: 1px solid rgba(55, 53, 47, 0.16); border-radius: 4px; color: inherit; text-decoration: none; }
+.bookmark-title { font-size: 0.875em; }
+.bookmark-href { margin-top: 4px; color: rgba(55, 53, 47, 0.65); font-size: 0.75em; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }
+figure img, figure video { max-width: 100%; }
+figcaption { margin-top: 0.3em; color: rgba(55, 53, 47, 0.65); font-size: 0.875em; }
+.highlight-gray, .block-color-gray { color: rgba(120, 119, 116, 1); }
+.highlight-brown, .block-color-brown { color: rgba(159, 107, 83, 1); }
+.highlight-orange, .block-color-orange { color: rgba(217, 115, 13, 1); }
+.highlight-yellow, .block-color-yellow { color: rgba(203, 145, 47, 1); }
+.highlight-green, .block-color-green { color: rgba(68, 131, 97, 1); }
+.highlight-blue, .block-color-blue { color: rgba(51, 126, 169, 1); }
+.highlight-purple, .block-color-purple { color: rgba(144, 101, 176, 1); }
+.highlight-pink, .block-color-pink { color: rgba(193, 76, 138, 1); }
+.highlight-red, .block-color-red { color: rgba(212, 76, 71, 1); }
+.highlight-gray_background, .block-color-gray_background { background: rgba(241, 241, 239, 1); }
+.highlight-brown_background, .block-color-brown_background { background: rgba(244, 238, 238, 1); }
+.highlight-orange_background, .block-color-orange_background { background: rgba(251, 236, 221, 1); }
+.highlight-yellow_background, .block-color-yellow_background { background: rgba(251, 243, 219, 1); }
+.highlight-green_background, .block-color-green_background { background: rgba(237, 243, 236, 1); }
+.highlight-blue_background, .block-color-blue_background { background: rgba(231, 243, 248, 1); }
+.highlight-purple_background, .block-color-purple_background { background: rgba(244, 240, 247, 0.8); }
+.highlight-pink_background, .block-color-pink_background { background: rgba(249, 238, 243, 0.8); }
+.highlight-red_background, .block-color-red_background { background: rgba(253, 235, 236, 1); }
+"#;
+
+
+/**
+ * 生成包含默认样式的完整HTML文档
+ */
+pub fn document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\"/>\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\"/>\n<title>{title}</title>\n<style>{STYLESHEET}</style>\n</head>\n<body>\n<article>\n<h1 class=\"page-title\">{title}</h1>\n{body}</article>\n</body>\n</html>\n",
+        title = escape(title),
+    )
+}
+
+/**
+ * 转义HTML特殊字符
+ */
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+
+/**
+ * HTML输出，颜色以class表示，样式见STYLESHEET
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Html {
+    synced_source: bool,
+    placeholder: Option<String>,
+}
+
+impl Html {
+    /**
+     * 是否在同步块的容器上以data-source标出来源
+     */
+    pub fn synced_source(mut self, annotate: bool) -> Self {
+        self.synced_source = annotate;
+        self
+    }
+
+    /**
+     * 未解析的块输出为template，{type}替换为块类型，{id}替换为块ID；默认不输出
+     */
+    pub fn placeholder(mut self, template: &str) -> Self {
+        self.placeholder = Some(template.to_string());
+        self
+    }
+
+    /**
+     * 按块类型的html模板填充文本和状态
+     */
+    fn format(be: &BlockElement, text: &str, status: &str) -> String {
+        fill(&be.line_type.get_str("html").unwrap_or("{}").replace("{status}", status), text) + "\n"
+    }
+
+    /**
+     * 按块类型的html模板填充文本、状态和子块，模板中没有子块位置时子块缩进排在块之后
+     */
+    fn nest(be: &BlockElement, text: &str, status: &str, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let format = be.line_type.get_str("html").unwrap_or("{}").replace("{status}", status);
+        match format.contains("{child}") {
+            true => fill_child(out, &format, text, ("\n", "\n"), child)?,
+            false => fill_child(out, &(format + "{child}"), text, ("\n<div class=\"indented\">\n", "\n</div>"), child)?,
+        }
+        out.write_str("\n")
+    }
+}
+
+impl Renderer for Html {
+    fn escape(&self, text: &str) -> String {
+        escape(text).replace('\n', "<br/>")
+    }
+
+    fn inline(&self, fragment: &FragmentText) -> String {
+        let text = fragment.inline.text().unwrap_or_else(|| fragment.text.to_string());
+        fill(fragment.inline.get_str("html").unwrap_or("{}"), &self.escape(&text))
+    }
+
+    fn annotation(&self, anno: &Annotation, text: String) -> String {
+        fill(anno.get_str("html").unwrap_or("{}"), &text)
+    }
+
+    fn color(&self, color: &AnnoColor, text: String) -> String {
+        let format = Annotation::Color(AnnoColor::Default).get_str("html").unwrap();
+        fill(&format.replace("{color}", &color.to_string()), &text)
+    }
+
+    fn fragment(&self, fragment: &FragmentText) -> String {
+        render_fragment(self, fragment)
+    }
+
+    fn link(&self, href: &str, text: String) -> String {
+        format!(r#"<a href="{}">{}</a>"#, escape(href), text)
+    }
+
+    fn line(&self, line: &[FragmentText]) -> String {
+        render_line(self, line)
+    }
+
+    fn paragraph(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Html::nest(be, &text, "", child, out)
+    }
+
+    fn heading(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let anchor = match be.anchor.is_empty() {
+            true => String::default(),
+            false => format!(" id=\"{}\"", escape(&be.anchor)),
+        };
+        if !be.status.as_bool().unwrap_or_default() {
+            return Html::nest(be, &text, &anchor, child, out);
+        }
+
+        // 可折叠标题
+        let heading = Html::format(be, &text, &anchor);
+        out.write_str(&format!("<details><summary>{}</summary>\n", heading.trim_end()))?;
+        child(out)?;
+        out.write_str("</details>\n")
+    }
+
+    fn list_item(&self, be: &BlockElement, _index: usize, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Html::nest(be, &text, "", child, out)
+    }
+
+    fn to_do(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let status = if be.status.as_bool().unwrap_or_default() { " checked" } else { "" };
+        Html::nest(be, &text, status, child, out)
+    }
+
+    fn toggle(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Html::nest(be, &text, "", child, out)
+    }
+
+    fn callout(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Html::nest(be, &text, &icon(be), child, out)
+    }
+
+    fn quote(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Html::nest(be, &text, "", child, out)
+    }
+
+    fn code(&self, be: &BlockElement, code: String) -> String {
+        Html::format(be, &escape(&code), &code_class(be.status.as_str().unwrap_or_default()))
+    }
+
+    fn divider(&self, be: &BlockElement) -> String {
+        Html::format(be, "", "")
+    }
+
+    fn equation(&self, be: &BlockElement, expression: String) -> String {
+        Html::format(be, &escape(&expression), "")
+    }
+
+    /**
+     * 图片、视频和音频放在figure中，说明文字作为figcaption；文件输出为链接
+     */
+    fn media(&self, be: &BlockElement, media: &Media, caption: String) -> String {
+        let format = be.line_type.get_str("html").unwrap().replace("{url}", &escape(&media.url));
+        if !matches!(be.line_type, BlockType::Image|BlockType::Video|BlockType::Audio) {
+            let label = if caption.is_empty() { escape(&media.name) } else { caption };
+            return "<p>".to_string() + &fill(&format, &label) + "</p>\n";
+        }
+
+        let alt = be.line.iter().map(|t| t.text.as_str()).collect::<String>();
+        let caption = if caption.is_empty() { caption } else { format!("<figcaption>{}</figcaption>", caption) };
+        format!("<figure>{}{}</figure>\n", fill(&format, &escape(&alt)), caption)
+    }
+
+    /**
+     * 输出为链接卡片，标题下显示链接地址，说明文字放在卡片之后
+     */
+    fn bookmark(&self, _be: &BlockElement, media: &Media, caption: String) -> String {
+        let caption = if caption.is_empty() { caption } else { format!("<figcaption>{}</figcaption>", caption) };
+        format!(
+            r#"<figure><a class="bookmark" href="{url}"><div class="bookmark-title">{}</div><div class="bookmark-href">{url}</div></a>{}</figure>"#,
+            escape(&media.name), caption, url = escape(&media.url),
+        ) + "\n"
+    }
+
+    fn unsupported(&self, be: &BlockElement, block_type: &str) -> String {
+        match &self.placeholder {
+            Some(template) => placeholder(template, be, &escape(block_type)) + "\n",
+            None => String::default(),
+        }
+    }
+
+    fn synced(&self, _be: &BlockElement, source: &str, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        if !self.synced_source {
+            return child(out);
+        }
+
+        out.write_str(&format!("<div class=\"synced-block\" data-source=\"{}\">\n", escape(source)))?;
+        child(out)?;
+        out.write_str("</div>\n")
+    }
+
+    fn table_of_contents(&self, _be: &BlockElement, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str("<nav class=\"table-of-contents\">\n")?;
+        child(out)?;
+        out.write_str("</nav>\n")
+    }
+
+    /**
+     * 未查询时只输出标题
+     */
+    fn database(&self, be: &BlockElement, title: String, table: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        if be.child.is_empty() {
+            return out.write_str(&Html::format(be, &title, ""));
+        }
+
+        out.write_str(&format!("<div class=\"database\">\n<p class=\"database-title\">{}</p>\n", title))?;
+        table(out)?;
+        out.write_str("</div>\n")
+    }
+
+    /**
+     * 各栏按宽度比例横向排列
+     */
+    fn columns(&self, _be: &BlockElement, columns: Vec<(f64, Child)>, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str("<div class=\"column-list\">\n")?;
+        for (width, column) in columns {
+            out.write_str(&format!("<div class=\"column\" style=\"flex: {:.4} 1 0\">\n", width))?;
+            column(out)?;
+            out.write_str("</div>\n")?;
+        }
+        out.write_str("</div>\n")
+    }
+
+    /**
+     * 首行表头放在thead中，首列表头使用th
+     */
+    fn table(&self, be: &BlockElement, rows: Vec<Vec<String>>) -> String {
+        let (_, column_header, row_header) = be.table_header();
+        let line = |row: Vec<String>, head: bool| {
+            let cells: String = row.into_iter().enumerate()
+                .map(|(i, cell)| if head || (row_header && i == 0) { format!("<th>{}</th>", cell) } else { format!("<td>{}</td>", cell) })
+                .collect();
+            "<tr>".to_string() + &cells + "</tr>\n"
+        };
+
+        let mut rows = rows.into_iter();
+        let head = match rows.next() {
+            Some(row) if column_header => "<thead>\n".to_string() + &line(row, true) + "</thead>\n",
+            Some(row) => line(row, false),
+            None => String::default(),
+        };
+        let body: String = rows.map(|row| line(row, false)).collect();
+
+        match (column_header, head.is_empty()) {
+            (_, true) => "<table></table>\n".to_string(),
+            (true, false) => format!("<table>\n{}<tbody>\n{}</tbody>\n</table>\n", head, body),
+            (false, false) => format!("<table>\n<tbody>\n{}{}</tbody>\n</table>\n", head, body),
+        }
+    }
+
+    /**
+     * 在块的第一个标签上添加颜色class
+     */
+    fn block_color(&self, color: &AnnoColor, block: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let mut tagged = FirstTag { out, class: format!("block-color-{}", color), head: Some(String::default()) };
+        block(&mut tagged)?;
+        tagged.flush()
+    }
+
+    fn list(&self, line_type: &BlockType, items: &[BlockElement], out: &mut dyn fmt::Write) -> fmt::Result {
+        let (open, close) = match line_type {
+            BlockType::NumberedListItem => ("<ol>", "</ol>"),
+            BlockType::ToDo => ("<ul class=\"to-do-list\">", "</ul>"),
+            _ => ("<ul>", "</ul>"),
+        };
+        out.write_str(open)?;
+        out.write_str("\n")?;
+        for (i, be) in items.iter().enumerate() {
+            render_item(self, be, i + 1, out)?;
+        }
+        out.write_str(close)?;
+        out.write_str("\n")
+    }
+
+    /**
+     * 各块直接拼接
+     */
+    fn separator(&self, _previous: Option<&BlockType>, _next: &BlockType) -> Option<&str> {
+        None
+    }
+}
+
+/**
+ * 暂存输出直到第一个标签结束，为其添加class后再写出，之后的输出直接写出
+ */
+struct FirstTag<'a> {
+    out: &'a mut dyn fmt::Write,
+    class: String,
+    head: Option<String>,
+}
+
+impl FirstTag<'_> {
+    fn flush(&mut self) -> fmt::Result {
+        match self.head.take() {
+            Some(head) => self.out.write_str(&add_class(&head, &self.class)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Write for FirstTag<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.head.as_mut() {
+            Some(head) => {
+                head.push_str(s);
+                if head.contains('>') { self.flush() } else { Ok(()) }
+            },
+            None => self.out.write_str(s),
+        }
+    }
+}
+
+/**
+ * 为第一个标签添加class，已有class属性时追加到其中，自闭合标签的属性加在/>之前
+ */
+fn add_class(html: &str, class: &str) -> String {
+    let (start, end) = match (html.find('<'), html.find('>')) {
+        (Some(start), Some(end)) if start < end && !html[start..].starts_with("<!") => (start, end),
+        _ => return html.to_string(),
+    };
+
+    let tag = &html[start..end];
+    let tag = match tag.find(" class=\"") {
+        Some(at) => {
+            let at = at + " class=\"".len();
+            tag[..at].to_string() + class + " " + &tag[at..]
+        },
+        None => match tag.strip_suffix('/') {
+            Some(tag) => format!("{} class=\"{}\"/", tag, class),
+            None => format!("{} class=\"{}\"", tag, class),
+        },
+    };
+    html[..start].to_string() + &tag + &html[end..]
+}
+
+/**
+ * 代码块语言对应的class名称
+ */
+fn code_class(language: &str) -> String {
+    match language {
+        "plain text" => "plaintext".to_string(),
+        "c++" => "cpp".to_string(),
+        "c#" => "csharp".to_string(),
+        "f#" => "fsharp".to_string(),
+        _ => language.replace([' ', '/', '.'], "-"),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_on_plain_tag() {
+        assert_eq!(add_class("<p>text</p>\n", "block-color-red"), "<p class=\"block-color-red\">text</p>\n");
+    }
+
+    #[test]
+    fn class_merged_into_existing() {
+        assert_eq!(
+            add_class("<p class=\"page-link\"><a href=\"x\">t</a></p>", "block-color-red"),
+            "<p class=\"block-color-red page-link\"><a href=\"x\">t</a></p>",
+        );
+    }
+
+    #[test]
+    fn class_on_self_closing_tag() {
+        assert_eq!(add_class("<hr/>\n", "block-color-gray"), "<hr class=\"block-color-gray\"/>\n");
+    }
+
+    #[test]
+    fn class_on_streamed_block() {
+        let mut list = super::super::super::block::Block::from_markdown("> quote\n>\n> more").inner;
+        list[0].color = AnnoColor::Blue;
+        assert!(Html::default().document(&list).starts_with("<blockquote class=\"block-color-blue\">quote"));
+    }
+
+    #[test]
+    fn class_skips_comments() {
+        assert_eq!(add_class("<!-- x -->", "c"), "<!-- x -->");
+    }
+}
@@ -0,0 +1,458 @@
+use strum::EnumProperty;
+use std::fmt;
+
+use super::{Renderer, Child, Indent, fill, fill_child, placeholder, icon, nest_item, surround, render_item, render_line, encode_href};
+use super::super::{block::{BlockElement, FragmentText, Media}, text::*};
+
+
+/**
+ * 转义行内文本中的Markdown符号
+ */
+pub fn escape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut output = String::with_capacity(text.len());
+    for (i, c) in chars.iter().enumerate() {
+        let escape = match c {
+            '\\' | '`' | '*' | '[' | ']' | '<' | '|' | '~' => true,
+            // 单词内部的下划线不构成强调
+            '_' => !(i > 0 && chars[i - 1].is_alphanumeric() && chars.get(i + 1).is_some_and(|c| c.is_alphanumeric())),
+            // 只有构成实体引用时才需要转义
+            '&' => {
+                let len = chars[i + 1..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == '#').count();
+                len > 0 && chars.get(i + 1 + len) == Some(&';')
+            },
+            _ => false,
+        };
+        if escape {
+            output.push('\\');
+        }
+        output.push(*c);
+    }
+
+    output
+}
+
+/**
+ * 转义行首会被识别为标题、引用、列表或分隔线的符号
+ * 行首的空格和制表符去掉：缩进四格以上会变成代码块，而段落行首的空白本来就不会显示
+ */
+pub fn escape_line_start(text: &str) -> String {
+    text.split('\n').map(|line| {
+        let line = line.trim_start_matches([' ', '\t']);
+        let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+        let at = match line.chars().next() {
+            Some('#' | '>' | '+' | '-' | '=') => Some(0),
+            Some(_) if digits > 0 && matches!(line[digits..].chars().next(), Some('.' | ')')) => Some(digits),
+            _ => None,
+        };
+        match at {
+            Some(at) => line[..at].to_string() + "\\" + &line[at..],
+            None => line.to_string(),
+        }
+    }).collect::<Vec<String>>().join("\n")
+}
+
+/**
+ * 表格单元格中的竖线即使位于行内代码中也会被当作分隔符
+ */
+pub fn escape_cell(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut escaped = false;
+    for c in text.chars() {
+        if c == '|' && !escaped {
+            output.push('\\');
+        }
+        escaped = c == '\\' && !escaped;
+        output.push(c);
+    }
+
+    output
+}
+
+/**
+ * 管道表格，Markdown表格必须有表头，没有表头时以空行代替
+ */
+pub fn pipe_table(rows: Vec<Vec<String>>, has_header: bool) -> String {
+    let width = rows.first().map(|row| row.len()).unwrap_or_default();
+    if width == 0 {
+        return String::default();
+    }
+
+    let line = |cells: &[String]| {
+        "| ".to_string() + &cells.iter().map(|cell| escape_cell(cell.trim())).collect::<Vec<String>>().join(" | ") + " |"
+    };
+    let mut rows = rows.into_iter();
+    let header = if has_header { rows.next().unwrap_or_default() } else { vec![String::default(); width] };
+
+    let mut output = vec![line(&header), line(&vec!["---".to_string(); width])];
+    output.extend(rows.map(|row| line(&row)));
+    output.join("\n")
+}
+
+/**
+ * 行内代码的内容不转义，反引号的数量多于代码中最长的连续反引号
+ */
+pub fn code_span(code: &str) -> String {
+    let fence = "`".repeat(backtick_run(code) + 1);
+    let pad = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+    fence.clone() + pad + code + pad + &fence
+}
+
+/**
+ * 代码块的围栏，代码中含有```时加长
+ */
+pub fn code_fence(code: &str) -> String {
+    "`".repeat(backtick_run(code).max(2) + 1)
+}
+
+fn backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(|s| s.len()).max().unwrap_or_default()
+}
+
+/**
+ * 链接，地址中的空格和括号会截断链接，需要编码
+ */
+pub fn link(href: &str, text: String) -> String {
+    format!("[{}]({})", text, encode_href(href))
+}
+
+/**
+ * 片段的正文，行内代码和公式保持原文，其余文本转义
+ */
+pub fn inline(fragment: &FragmentText, key: &str) -> String {
+    let format = fragment.inline.get_str(key).unwrap_or("{}");
+    let text = fragment.inline.text().unwrap_or_else(|| fragment.text.to_string());
+    if matches!(fragment.inline, Inline::Equation(_)) || fragment.annotation.contains(&Annotation::Code) {
+        return fill(format, &text);
+    }
+
+    fill(format, &escape(&text))
+}
+
+
+/**
+ * 分栏依次输出为多个部分，跳过没有输出的栏，divider为真时各部分之间加分隔线
+ */
+pub fn sections(columns: Vec<(f64, Child)>, divider: bool, out: &mut dyn fmt::Write) -> fmt::Result {
+    let mut written = false;
+    for (_, column) in columns {
+        let lead = match (written, divider) {
+            (false, _) => "",
+            (true, true) => "\n\n---\n\n",
+            (true, false) => "\n\n",
+        };
+        let mut section = Indent::new(out, "").lead(lead);
+        column(&mut section)?;
+        written |= section.written();
+    }
+
+    Ok(())
+}
+
+
+/**
+ * 默认的Markdown输出，颜色、下划线、折叠等以内嵌HTML表示
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Markdown {
+    column_divider: bool,
+    synced_source: bool,
+    placeholder: Option<String>,
+}
+
+impl Markdown {
+    /**
+     * 分栏之间是否输出分隔线
+     */
+    pub fn column_divider(mut self, divider: bool) -> Self {
+        self.column_divider = divider;
+        self
+    }
+
+    /**
+     * 是否以HTML注释标出同步块的来源
+     */
+    pub fn synced_source(mut self, annotate: bool) -> Self {
+        self.synced_source = annotate;
+        self
+    }
+
+    /**
+     * 未解析的块输出为template，{type}替换为块类型，{id}替换为块ID；默认不输出
+     */
+    pub fn placeholder(mut self, template: &str) -> Self {
+        self.placeholder = Some(template.to_string());
+        self
+    }
+
+    /**
+     * 按块类型的md模板填充文本和状态
+     */
+    fn format(be: &BlockElement, text: &str, status: &str) -> String {
+        fill(&be.line_type.get_str("md").unwrap_or("{}").replace("{status}", status), text)
+    }
+
+    /**
+     * 子块以制表符缩进后接在块之后，子块之间不留空行
+     */
+    fn nest(paragraph: &str, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str("\n")?;
+        out.write_str(paragraph)?;
+        child(&mut Indent::new(out, "\t").blank(None).lead("\n"))?;
+        out.write_str("\n")
+    }
+}
+
+impl Renderer for Markdown {
+    fn inline(&self, fragment: &FragmentText) -> String {
+        inline(fragment, "md")
+    }
+
+    fn annotation(&self, anno: &Annotation, text: String) -> String {
+        fill(anno.get_str("md").unwrap_or("{}"), &text)
+    }
+
+    fn color(&self, color: &AnnoColor, text: String) -> String {
+        let format = Annotation::Color(AnnoColor::Default).get_str("md").unwrap();
+        fill(&format.replace("{color}", color.get_str("md").unwrap_or_default()), &text)
+    }
+
+    /**
+     * 同时加粗和斜体时，后一个样式改用下划线语法以免符号粘连
+     */
+    fn fragment(&self, fragment: &FragmentText) -> String {
+        let mut text = self.inline(fragment);
+        let mut conflict = false;
+        for anno in fragment.annotation.iter() {
+            text = match anno {
+                Annotation::Bold|Annotation::Italic if conflict => fill(anno.get_str("mdrpl").unwrap(), &text),
+                Annotation::Bold|Annotation::Italic => {
+                    conflict = true;
+                    self.annotation(anno, text)
+                },
+                Annotation::Code => code_span(&text),
+                Annotation::Color(AnnoColor::Default | AnnoColor::Other(_)) => text,
+                Annotation::Color(c) => self.color(c, text),
+                _ => self.annotation(anno, text),
+            };
+        }
+
+        text
+    }
+
+    fn line(&self, line: &[FragmentText]) -> String {
+        escape_line_start(&render_line(self, line))
+    }
+
+    fn paragraph(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Markdown::nest(&Markdown::format(be, &text, ""), child, out)
+    }
+
+    fn heading(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str(&("\n".to_string() + &Markdown::format(be, &text, "") + "\n"))?;
+        surround(out, "", child, "\n")
+    }
+
+    fn list_item(&self, be: &BlockElement, index: usize, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let format = be.line_type.get_str("md").unwrap().replace("{index}", &index.to_string());
+        let width = format.find("{}").unwrap_or_default();
+        nest_item(be, &fill(&format, &text), child, width, out)
+    }
+
+    fn to_do(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let status = if be.status.as_bool().unwrap_or_default() { "x" } else { " " };
+        nest_item(be, &Markdown::format(be, &text, status), child, 2, out)
+    }
+
+    fn toggle(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str("\n")?;
+        fill_child(out, be.line_type.get_str("md").unwrap_or("{}"), &text, ("", "\n"), child)?;
+        out.write_str("\n")
+    }
+
+    fn callout(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Markdown::nest(&Markdown::format(be, &text, &icon(be)).replace('\n', "<br/>"), child, out)
+    }
+
+    /**
+     * 子块接在引用标记之后
+     */
+    fn quote(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str(&("\n".to_string() + &Markdown::format(be, &text, "").replace('\n', "<br/>")))?;
+        surround(out, "\n>", child, "")?;
+        out.write_str("\n")
+    }
+
+    fn code(&self, be: &BlockElement, code: String) -> String {
+        let format = be.line_type.get_str("md").unwrap().replace("```", &code_fence(&code));
+        "\n".to_string() + &fill(&format.replace("{status}", be.status.as_str().unwrap_or_default()), &code) + "\n"
+    }
+
+    fn divider(&self, be: &BlockElement) -> String {
+        "\n".to_string() + &Markdown::format(be, "", "") + "\n"
+    }
+
+    fn equation(&self, be: &BlockElement, expression: String) -> String {
+        "\n".to_string() + &Markdown::format(be, &expression, "") + "\n"
+    }
+
+    /**
+     * 图片输出为图片语法，其他媒体输出为链接，没有说明文字时以文件名代替
+     */
+    fn media(&self, be: &BlockElement, media: &Media, caption: String) -> String {
+        let label = if caption.is_empty() { self.escape(&media.name) } else { caption };
+        let format = be.line_type.get_str("md").unwrap().replace("{url}", &encode_href(&media.url));
+        "\n".to_string() + &fill(&format, &label) + "\n"
+    }
+
+    /**
+     * 没有标题时以说明文字作为链接文本
+     */
+    fn bookmark(&self, be: &BlockElement, media: &Media, caption: String) -> String {
+        let label = if media.name == media.url && !caption.is_empty() { caption } else { self.escape(&media.name) };
+        let format = be.line_type.get_str("md").unwrap().replace("{url}", &encode_href(&media.url));
+        "\n".to_string() + &fill(&format, &label) + "\n"
+    }
+
+    fn unsupported(&self, be: &BlockElement, block_type: &str) -> String {
+        match &self.placeholder {
+            Some(template) => "\n".to_string() + &placeholder(template, be, block_type) + "\n",
+            None => String::default(),
+        }
+    }
+
+    fn synced(&self, _be: &BlockElement, source: &str, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        if !self.synced_source {
+            return child(out);
+        }
+
+        out.write_str(&format!("\n<!-- synced_block: {} -->\n", source))?;
+        child(&mut Indent::new(out, ""))?;
+        out.write_str("\n<!-- /synced_block -->\n")
+    }
+
+    fn table_of_contents(&self, _be: &BlockElement, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        child(out)
+    }
+
+    fn database(&self, be: &BlockElement, title: String, table: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str(&("\n".to_string() + &Markdown::format(be, &title, "") + "\n"))?;
+        surround(out, "\n", table, "\n")
+    }
+
+    /**
+     * Markdown没有分栏，各栏按顺序排列
+     */
+    fn columns(&self, _be: &BlockElement, columns: Vec<(f64, Child)>, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str("\n")?;
+        sections(columns, self.column_divider, out)?;
+        out.write_str("\n")
+    }
+
+    /**
+     * 单元格内的换行改为<br/>
+     */
+    fn table(&self, be: &BlockElement, rows: Vec<Vec<String>>) -> String {
+        let rows = rows.into_iter().map(|row| row.into_iter().map(|cell| cell.replace('\n', "<br/>")).collect()).collect();
+        "\n".to_string() + &pipe_table(rows, be.table_header().1) + "\n"
+    }
+
+    fn block_color(&self, color: &AnnoColor, block: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let format = Annotation::Color(AnnoColor::Default).get_str("md").unwrap().replace("{color}", color.get_str("md").unwrap_or_default());
+        let (open, close) = format.split_once("{}").unwrap_or((&format, ""));
+        out.write_str(&("\n".to_string() + open))?;
+        block(out)?;
+        out.write_str(&(close.to_string() + "\n"))
+    }
+
+    /**
+     * 没有文本的块输出为空行
+     */
+    fn block(&self, be: &BlockElement, out: &mut dyn fmt::Write) -> fmt::Result {
+        if be.line.is_empty() && be.media.is_none() && be.line_type.text_key().is_some() {
+            return out.write_str("\n<br/>");
+        }
+
+        render_item(self, be, 1, out)
+    }
+
+    /**
+     * 同一列表的列表项逐行排列，列表前后以空行分隔
+     */
+    fn list(&self, _line_type: &BlockType, items: &[BlockElement], out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str("\n")?;
+        for (i, be) in items.iter().enumerate() {
+            render_item(self, be, i + 1, &mut Indent::new(out, "").lead(if i > 0 { "\n" } else { "" }))?;
+        }
+        out.write_str("\n")
+    }
+
+    /**
+     * 块之间以一个空行分隔，整个文档去掉首尾空白
+     * 两个同类列表之间的块没有输出时以HTML注释隔开，否则两个列表会被合并为一个
+     */
+    fn separator(&self, previous: Option<&BlockType>, next: &BlockType) -> Option<&str> {
+        Some(match previous {
+            None => "",
+            Some(previous) if next.is_list() && previous == next => "\n<!-- -->\n\n",
+            Some(_) => "\n\n",
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::block::Block;
+
+    #[test]
+    fn lists_separated_by_empty_block() {
+        let mut list = Block::from_markdown("1. n1").inner;
+        list.push(BlockElement::from_type(BlockType::Unsupported));
+        list.extend(Block::from_markdown("1. n after break").inner);
+
+        assert_eq!(Markdown::default().document(&list), "1. n1\n<!-- -->\n\n1. n after break");
+    }
+
+    #[test]
+    fn placeholder_template() {
+        let value = serde_json::json!({ "id": "c02fc1d3-db8b-45c5-a222-27595b15aea7", "has_children": false, "type": "ai_block", "ai_block": {} });
+        let list = vec![BlockElement::new(&value).unwrap()];
+
+        assert_eq!(Markdown::default().document(&list), "");
+        assert_eq!(
+            Markdown::default().placeholder("<!-- {type} {id} -->").document(&list),
+            "<!-- ai_block c02fc1d3-db8b-45c5-a222-27595b15aea7 -->",
+        );
+    }
+
+    #[test]
+    fn escape_inline() {
+        assert_eq!(escape("a*b* [x](y) `c` <d> a|b ~s~"), "a\\*b\\* \\[x\\](y) \\`c\\` \\<d> a\\|b \\~s\\~");
+        assert_eq!(escape("snake_case _em_"), "snake_case \\_em\\_");
+        assert_eq!(escape("AT&T &amp; &#123;"), "AT&T \\&amp; \\&#123;");
+        assert_eq!(escape("back\\slash"), "back\\\\slash");
+    }
+
+    #[test]
+    fn escape_block_start() {
+        assert_eq!(escape_line_start("# title"), "\\# title");
+        assert_eq!(escape_line_start("> quote\n- item\n+ item\n==="), "\\> quote\n\\- item\n\\+ item\n\\===");
+        assert_eq!(escape_line_start("1. one\n2) two\n2024 year"), "1\\. one\n2\\) two\n2024 year");
+        assert_eq!(escape_line_start("plain text"), "plain text");
+    }
+
+    #[test]
+    fn escape_indentation() {
+        assert_eq!(escape_line_start("    indented code?"), "indented code?");
+        assert_eq!(escape_line_start("\tTab\n  - item"), "Tab\n\\- item");
+    }
+
+    #[test]
+    fn escape_cell_pipes() {
+        assert_eq!(escape_cell("a|b"), "a\\|b");
+        assert_eq!(escape_cell("a\\|b"), "a\\|b");
+        assert_eq!(escape_cell("`x|y`"), "`x\\|y`");
+    }
+}
@@ -0,0 +1,842 @@
+pub mod markdown;
+pub mod gfm;
+pub mod html;
+pub mod obsidian;
+
+pub use markdown::Markdown;
+pub use gfm::Gfm;
+pub use html::Html;
+pub use obsidian::Obsidian;
+
+use strum::EnumProperty;
+use super::{block::{BlockElement, FragmentText, Media}, text::*, get_property_value, Json};
+use std::{fmt::{self, Write}, io};
+
+
+/**
+ * 尚未渲染的子块，调用时写入传入的out
+ */
+pub type Child<'a> = &'a dyn Fn(&mut dyn fmt::Write) -> fmt::Result;
+
+/**
+ * 页面内容的输出格式，每种块和行内样式都对应一个可覆盖的方法
+ * 默认实现输出GitHub Flavored Markdown（见Gfm），其他格式只需覆盖不同的部分
+ * 含子块的块直接写入out，子块由child在需要的位置写入；其余块返回渲染结果
+ */
+pub trait Renderer {
+    /**
+     * 转义普通文本
+     */
+    fn escape(&self, text: &str) -> String {
+        markdown::escape(text)
+    }
+
+    /**
+     * 为文本添加颜色以外的行内样式
+     */
+    fn annotation(&self, anno: &Annotation, text: String) -> String {
+        fill(anno.get_str("gfm").unwrap_or("{}"), &text)
+    }
+
+    /**
+     * 为文本添加行内颜色，默认舍弃颜色
+     */
+    fn color(&self, color: &AnnoColor, text: String) -> String {
+        let _ = color;
+        text
+    }
+
+    /**
+     * 片段的正文，提及和行内公式按类型输出
+     */
+    fn inline(&self, fragment: &FragmentText) -> String {
+        markdown::inline(fragment, "gfm")
+    }
+
+    /**
+     * 强调符号内侧不能有空白，首尾空白移到符号之外
+     */
+    fn fragment(&self, fragment: &FragmentText) -> String {
+        let text = self.inline(fragment);
+        let core = text.trim();
+        if core.is_empty() {
+            return text;
+        }
+
+        let start = text.len() - text.trim_start().len();
+        let mut core = core.to_string();
+        let mut conflict = false;
+        for anno in fragment.annotation.iter() {
+            core = match anno {
+                Annotation::Bold|Annotation::Italic if conflict => fill(anno.get_str("mdrpl").unwrap(), &core),
+                Annotation::Bold|Annotation::Italic => {
+                    conflict = true;
+                    self.annotation(anno, core)
+                },
+                Annotation::Code => markdown::code_span(&core),
+                Annotation::Color(AnnoColor::Other(_)) => core,
+                Annotation::Color(c) => self.color(c, core),
+                _ => self.annotation(anno, core),
+            };
+        }
+
+        text[..start].to_string() + &core + &text[start + text.trim().len()..]
+    }
+
+    /**
+     * 为文本添加链接，href为原始地址
+     */
+    fn link(&self, href: &str, text: String) -> String {
+        markdown::link(href, text)
+    }
+
+    /**
+     * 换行使用反斜杠硬换行
+     */
+    fn line(&self, line: &[FragmentText]) -> String {
+        markdown::escape_line_start(&render_line(self, line)).replace('\n', "\\\n")
+    }
+
+    /**
+     * Markdown无法表示段落的缩进，子块按顺序排在段落之后
+     */
+    fn paragraph(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        gfm::join(&gfm::format(be, &text, ""), child, out)
+    }
+
+    fn heading(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        gfm::join(&gfm::format(be, &text, ""), child, out)
+    }
+
+    /**
+     * 无序及有序列表项，index为该项在所属列表中的序号（从1开始）
+     */
+    fn list_item(&self, be: &BlockElement, index: usize, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let format = be.line_type.get_str("gfm").unwrap().replace("{index}", &index.to_string());
+        let width = format.find("{}").unwrap_or_default();
+        nest_item(be, &fill(&format, &text), child, width, out)
+    }
+
+    fn to_do(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let status = if be.status.as_bool().unwrap_or_default() { "x" } else { " " };
+        nest_item(be, &gfm::format(be, &text, status), child, 2, out)
+    }
+
+    /**
+     * 折叠块输出为列表项
+     */
+    fn toggle(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        nest_item(be, &gfm::format(be, &text, ""), child, 2, out)
+    }
+
+    /**
+     * 标注块输出为引用，图标在文本之前
+     */
+    fn callout(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        gfm::join(gfm::format(be, &text, &icon(be)).trim_start(), child, &mut gfm::blockquote(out))
+    }
+
+    fn quote(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        gfm::join(&gfm::format(be, &text, ""), child, &mut gfm::blockquote(out))
+    }
+
+    /**
+     * 代码块，code为未经转义的原始文本
+     */
+    fn code(&self, be: &BlockElement, code: String) -> String {
+        let format = be.line_type.get_str("gfm").unwrap().replace("```", &markdown::code_fence(&code));
+        let language = match be.status.as_str().unwrap_or_default() {
+            "plain text" => "",
+            language => language,
+        };
+        fill(&format.replace("{status}", language), code.trim_end_matches('\n'))
+    }
+
+    fn divider(&self, be: &BlockElement) -> String {
+        gfm::format(be, "", "")
+    }
+
+    /**
+     * 公式块，expression为未经转义的原始文本
+     */
+    fn equation(&self, be: &BlockElement, expression: String) -> String {
+        gfm::format(be, &expression, "")
+    }
+
+    /**
+     * 图片、视频、音频及文件块，caption为渲染后的说明文字，没有说明文字时以文件名代替
+     */
+    fn media(&self, be: &BlockElement, media: &Media, caption: String) -> String {
+        let label = if caption.is_empty() { self.escape(&media.name) } else { caption };
+        fill(&be.line_type.get_str("gfm").unwrap().replace("{url}", &encode_href(&media.url)), &label)
+    }
+
+    /**
+     * 书签、嵌入和链接预览，media.name为链接的标题，没有标题时以说明文字作为链接文本
+     */
+    fn bookmark(&self, be: &BlockElement, media: &Media, caption: String) -> String {
+        let label = if media.name == media.url && !caption.is_empty() { caption } else { self.escape(&media.name) };
+        fill(&be.line_type.get_str("gfm").unwrap().replace("{url}", &encode_href(&media.url)), &label)
+    }
+
+    /**
+     * 同步块，source为内容来源的块ID，child为同步的内容，默认只输出内容
+     */
+    fn synced(&self, be: &BlockElement, source: &str, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let _ = (be, source);
+        child(out)
+    }
+
+    /**
+     * 内嵌数据库，title为渲染后的标题链接，table为查询结果的表格（未查询时没有输出）
+     */
+    fn database(&self, be: &BlockElement, title: String, table: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        gfm::join(&gfm::format(be, &title, ""), table, out)
+    }
+
+    /**
+     * 分栏，columns为每一栏的宽度比例和内容，默认各栏按顺序排列
+     */
+    fn columns(&self, be: &BlockElement, columns: Vec<(f64, Child)>, out: &mut dyn fmt::Write) -> fmt::Result {
+        let _ = be;
+        markdown::sections(columns, false, out)
+    }
+
+    /**
+     * 目录，child为标题链接列表
+     */
+    fn table_of_contents(&self, be: &BlockElement, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let _ = be;
+        child(out)
+    }
+
+    /**
+     * 未解析的块，block_type为其在Notion中的原始类型，默认不输出
+     */
+    fn unsupported(&self, be: &BlockElement, block_type: &str) -> String {
+        let _ = (be, block_type);
+        String::default()
+    }
+
+    /**
+     * 表格，rows为渲染后的单元格，每行已补齐到相同的列数
+     * 单元格内不能换行，硬换行改为空格
+     */
+    fn table(&self, be: &BlockElement, rows: Vec<Vec<String>>) -> String {
+        let rows = rows.into_iter().map(|row| row.into_iter().map(|cell| cell.replace("\\\n", " ")).collect()).collect();
+        markdown::pipe_table(rows, be.table_header().1)
+    }
+
+    /**
+     * 为整个块添加颜色，默认舍弃颜色
+     */
+    fn block_color(&self, color: &AnnoColor, block: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let _ = color;
+        block(out)
+    }
+
+    /**
+     * 渲染一组连续的同类列表项，同一列表的列表项之间只换行
+     */
+    fn list(&self, line_type: &BlockType, items: &[BlockElement], out: &mut dyn fmt::Write) -> fmt::Result {
+        let _ = line_type;
+        gfm::list_items(self, items, out)
+    }
+
+    fn block(&self, be: &BlockElement, out: &mut dyn fmt::Write) -> fmt::Result {
+        render_item(self, be, 1, out)
+    }
+
+    /**
+     * 两组块之间的分隔，previous为上一个有输出的块的类型（没有时为None）
+     * 返回None时各组直接拼接；否则各组首尾的换行被去掉，没有输出的组被跳过
+     */
+    fn separator(&self, previous: Option<&BlockType>, next: &BlockType) -> Option<&str> {
+        Some(gfm::separator(previous, next))
+    }
+
+    /**
+     * 逐组渲染块列表并立即写入out，页面和子块都由此输出
+     */
+    fn blocks(&self, list: &[BlockElement], out: &mut dyn fmt::Write) -> fmt::Result {
+        Stream::new(self, out).blocks(list)
+    }
+
+    /**
+     * 渲染整个页面的块列表
+     */
+    fn document(&self, list: &[BlockElement]) -> String {
+        let mut output = String::default();
+        let _ = self.blocks(list, &mut output);
+        output
+    }
+}
+
+/**
+ * 逐组写出块列表，连续的同类列表项为一组，其余块各自成组
+ * push可以多次调用，末尾的列表留到下一次push或finish时再写出，分页获取的列表不会被拆开
+ */
+pub struct Stream<'a, R: Renderer + ?Sized> {
+    renderer: &'a R,
+    out: &'a mut dyn fmt::Write,
+    // 上一个有输出的组的类型
+    previous: Option<BlockType>,
+    pending: Vec<BlockElement>,
+}
+
+impl<'a, R: Renderer + ?Sized> Stream<'a, R> {
+    pub fn new(renderer: &'a R, out: &'a mut dyn fmt::Write) -> Self {
+        Stream { renderer, out, previous: None, pending: Vec::new() }
+    }
+
+    /**
+     * 写出全部的块
+     */
+    pub fn blocks(&mut self, list: &[BlockElement]) -> fmt::Result {
+        for items in group(list) {
+            self.group(items)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * 写出新获取的一批块
+     */
+    pub fn push(&mut self, list: Vec<BlockElement>) -> fmt::Result {
+        let mut list: Vec<BlockElement> = std::mem::take(&mut self.pending).into_iter().chain(list).collect();
+        let split = match list.last() {
+            Some(last) if last.line_type.is_list() => list.len() - list.iter().rev().take_while(|be| be.line_type == last.line_type).count(),
+            _ => list.len(),
+        };
+        self.pending = list.split_off(split);
+        self.blocks(&list)
+    }
+
+    /**
+     * 写出push留下的列表
+     */
+    pub fn finish(mut self) -> fmt::Result {
+        let pending = std::mem::take(&mut self.pending);
+        self.blocks(&pending)
+    }
+
+    fn group(&mut self, items: &[BlockElement]) -> fmt::Result {
+        let renderer = self.renderer;
+        let line_type = &items[0].line_type;
+        let write = |out: &mut dyn fmt::Write| match line_type.is_list() {
+            true => renderer.list(line_type, items, out),
+            false => renderer.block(&items[0], out),
+        };
+
+        let separator = match renderer.separator(self.previous.as_ref(), line_type) {
+            Some(separator) => separator,
+            None => return write(self.out),
+        };
+        let mut out = Indent::new(self.out, "").lead(separator);
+        write(&mut out)?;
+        if out.written() {
+            self.previous = Some(line_type.clone());
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * 为每个非空行添加前缀的写入器，用于缩进子块或添加引用标记
+ * 开头的换行被丢弃，换行暂存到之后有内容时才写出，所以输出的首尾没有换行
+ */
+pub struct Indent<'a> {
+    out: &'a mut dyn fmt::Write,
+    prefix: &'a str,
+    // 空行的内容，None时连续的换行合并为一个
+    blank: Option<&'a str>,
+    // 有内容时先写出的文本
+    lead: &'a str,
+    newlines: usize,
+    written: bool,
+    line_start: bool,
+}
+
+impl<'a> Indent<'a> {
+    pub fn new(out: &'a mut dyn fmt::Write, prefix: &'a str) -> Self {
+        Indent { out, prefix, blank: Some(""), lead: "", newlines: 0, written: false, line_start: true }
+    }
+
+    /**
+     * 空行输出为blank，None时去掉空行
+     */
+    pub fn blank(mut self, blank: Option<&'a str>) -> Self {
+        self.blank = blank;
+        self
+    }
+
+    /**
+     * 第一段内容之前写出lead，没有内容时不写出
+     */
+    pub fn lead(mut self, lead: &'a str) -> Self {
+        self.lead = lead;
+        self
+    }
+
+    /**
+     * 首行接在已有的内容之后，不加前缀，只缩进之后的行
+     */
+    pub fn hanging(mut self) -> Self {
+        self.written = true;
+        self.line_start = false;
+        self
+    }
+
+    /**
+     * 是否已写出内容
+     */
+    pub fn written(&self) -> bool {
+        self.written
+    }
+}
+
+impl fmt::Write for Indent<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, piece) in s.split('\n').enumerate() {
+            if i > 0 && self.written {
+                self.newlines += 1;
+            }
+            if piece.is_empty() {
+                continue;
+            }
+
+            if !self.written {
+                self.out.write_str(self.lead)?;
+            }
+            for n in 0..self.newlines {
+                match (n, self.blank) {
+                    (0, _) => {},
+                    (_, Some(blank)) => self.out.write_str(blank)?,
+                    (_, None) => continue,
+                }
+                self.out.write_str("\n")?;
+            }
+            if self.newlines > 0 {
+                self.line_start = true;
+            }
+            if self.line_start {
+                self.out.write_str(self.prefix)?;
+            }
+            self.out.write_str(piece)?;
+            self.newlines = 0;
+            self.written = true;
+            self.line_start = false;
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * 子块有输出时才在其前后写出head和tail，子块首尾的换行被去掉
+ */
+pub fn surround(out: &mut dyn fmt::Write, head: &str, child: Child, tail: &str) -> fmt::Result {
+    let mut inner = Indent::new(out, "").lead(head);
+    child(&mut inner)?;
+    if inner.written() {
+        out.write_str(tail)?;
+    }
+
+    Ok(())
+}
+
+/**
+ * 将text填入模板的`{}`处，模板中的`{child}`处写入子块
+ * 子块有输出时在其前后写出head和tail，模板中没有`{child}`时子块接在模板之后
+ */
+pub fn fill_child(out: &mut dyn fmt::Write, format: &str, text: &str, (head, tail): (&str, &str), child: Child) -> fmt::Result {
+    let (before, after) = format.split_once("{child}").unwrap_or((format, ""));
+    match before.contains("{}") {
+        true => out.write_str(&fill(before, text))?,
+        false => out.write_str(before)?,
+    }
+    surround(out, head, child, tail)?;
+    match before.contains("{}") {
+        true => out.write_str(after),
+        false => out.write_str(&fill(after, text)),
+    }
+}
+
+/**
+ * 将文本填入模板的`{}`处，文本中的花括号不会被再次替换
+ */
+pub fn fill(format: &str, text: &str) -> String {
+    match format.split_once("{}") {
+        Some((head, tail)) => head.to_string() + text + tail,
+        None => format.to_string(),
+    }
+}
+
+/**
+ * 填充未解析块的占位模板，{type}为块类型，{id}为块ID
+ */
+pub fn placeholder(template: &str, be: &BlockElement, block_type: &str) -> String {
+    let id = be.id.as_ref().map(|id| id.to_string()).unwrap_or_default();
+    template.replace("{id}", &id).replace("{type}", block_type)
+}
+
+/**
+ * 按GitHub的规则由标题生成锚点：转为小写，去掉标点，空格改为连字符，中文等字符原样保留
+ */
+pub fn slug(text: &str) -> String {
+    text.trim().to_lowercase().chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/**
+ * 编码Markdown链接地址中的空格和括号
+ */
+pub fn encode_href(href: &str) -> String {
+    href.replace(' ', "%20").replace('(', "%28").replace(')', "%29")
+}
+
+/**
+ * 编码本地文件的相对路径，文件名中的#、?、%等保留字符不能出现在链接中，只保留路径分隔符和非ASCII字符
+ */
+pub fn encode_path(path: &str) -> String {
+    let mut output = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '-' | '.' | '_' | '~' | '/' => output.push(c),
+            c if c.is_ascii() => output += &format!("%{:02X}", c as u8),
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+/**
+ * 依次为片段添加行内样式，颜色交由color处理
+ */
+pub fn render_fragment<R: Renderer + ?Sized>(renderer: &R, fragment: &FragmentText) -> String {
+    let mut text = renderer.inline(fragment);
+    for anno in fragment.annotation.iter() {
+        text = match anno {
+            Annotation::Color(AnnoColor::Default | AnnoColor::Other(_)) => text,
+            Annotation::Color(c) => renderer.color(c, text),
+            _ => renderer.annotation(anno, text),
+        };
+    }
+
+    text
+}
+
+/**
+ * 标注块的图标
+ */
+pub fn icon(be: &BlockElement) -> String {
+    get_property_value(&be.status, None).unwrap_or(&Json::default()).as_str().unwrap_or_default().to_string()
+}
+
+/**
+ * 渲染一行文本，相同样式的相邻片段先合并，指向同一地址的连续片段共用一个链接
+ */
+pub fn render_line<R: Renderer + ?Sized>(renderer: &R, line: &[FragmentText]) -> String {
+    let line = FragmentText::merge(line);
+    let mut output = String::default();
+    let mut i = 0;
+    while i < line.len() {
+        let href = &line[i].href;
+        let len = line[i..].iter().take_while(|t| t.href == *href).count();
+        let text: String = line[i..i + len].iter().map(|t| renderer.fragment(t)).collect();
+        output += &if href.is_empty() { text } else { renderer.link(href, text) };
+        i += len;
+    }
+
+    output
+}
+
+/**
+ * 列表项的子块缩进到列表标记之后，续行同样缩进
+ * 子块不是列表时需要空行分隔，否则会被并入列表项的文本
+ */
+pub fn nest_item(be: &BlockElement, item: &str, child: Child, width: usize, out: &mut dyn fmt::Write) -> fmt::Result {
+    let indent = " ".repeat(width);
+    Indent::new(out, &indent).hanging().write_str(item)?;
+    let lead = match be.child.first() {
+        None => return Ok(()),
+        Some(first) if first.line_type.is_list() => "\n",
+        Some(_) => "\n\n",
+    };
+
+    child(&mut Indent::new(out, &indent).lead(lead))
+}
+
+/**
+ * 按块类型分发到对应的渲染方法，index为列表项的序号，子块在渲染方法需要时才渲染
+ */
+pub fn render_item<R: Renderer + ?Sized>(renderer: &R, be: &BlockElement, index: usize, out: &mut dyn fmt::Write) -> fmt::Result {
+    match be.color {
+        AnnoColor::Default | AnnoColor::Other(_) => dispatch(renderer, be, index, out),
+        _ => renderer.block_color(&be.color, &|out: &mut dyn fmt::Write| dispatch(renderer, be, index, out), out),
+    }
+}
+
+fn dispatch<R: Renderer + ?Sized>(renderer: &R, be: &BlockElement, index: usize, out: &mut dyn fmt::Write) -> fmt::Result {
+    let child = |out: &mut dyn fmt::Write| renderer.blocks(&be.child, out);
+    let raw = || be.line.iter().map(|t| t.text.as_str()).collect::<String>();
+
+    use BlockType::*;
+    match be.line_type {
+        Paragraph => renderer.paragraph(be, renderer.line(&be.line), &child, out),
+        Heading1|Heading2|Heading3 => renderer.heading(be, renderer.line(&be.line), &child, out),
+        BulletedListItem|NumberedListItem => renderer.list_item(be, index, renderer.line(&be.line), &child, out),
+        ToDo => renderer.to_do(be, renderer.line(&be.line), &child, out),
+        Toggle => renderer.toggle(be, renderer.line(&be.line), &child, out),
+        Callout => renderer.callout(be, renderer.line(&be.line), &child, out),
+        Quote => renderer.quote(be, renderer.line(&be.line), &child, out),
+        Code => out.write_str(&renderer.code(be, raw())),
+        Divider => out.write_str(&renderer.divider(be)),
+        Equation => out.write_str(&renderer.equation(be, raw())),
+        Table => out.write_str(&renderer.table(be, table_rows(renderer, be))),
+        ColumnList => {
+            let columns: Vec<(f64, _)> = columns(be).into_iter()
+                .map(|(width, column)| (width, move |out: &mut dyn fmt::Write| renderer.blocks(&column.child, out)))
+                .collect();
+            renderer.columns(be, columns.iter().map(|(width, column)| (*width, column as Child)).collect(), out)
+        },
+        Column => child(out),
+        ChildPage|LinkToPage => renderer.paragraph(be, renderer.line(&be.line), &child, out),
+        ChildDatabase => renderer.database(be, renderer.line(&be.line), &child, out),
+        TableOfContents => renderer.table_of_contents(be, &child, out),
+        Unsupported => out.write_str(&renderer.unsupported(be, be.unsupported_type().unwrap_or_default())),
+        SyncedBlock => renderer.synced(be, be.synced_source().as_deref().unwrap_or_default(), &child, out),
+        Bookmark|Embed|LinkPreview => match &be.media {
+            Some(media) => out.write_str(&renderer.bookmark(be, media, renderer.line(&be.line))),
+            None => Ok(()),
+        },
+        Image|Video|File|Pdf|Audio => match &be.media {
+            Some(media) => out.write_str(&renderer.media(be, media, renderer.line(&be.line))),
+            None => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/**
+ * 渲染表格的每个单元格，列数不足的行以空单元格补齐
+ */
+fn table_rows<R: Renderer + ?Sized>(renderer: &R, be: &BlockElement) -> Vec<Vec<String>> {
+    let mut rows: Vec<Vec<String>> = be.child.iter()
+        .filter(|row| matches!(row.line_type, BlockType::TableRow))
+        .map(|row| row.cells.iter().map(|cell| renderer.line(cell)).collect())
+        .collect();
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or_default().max(be.table_header().0);
+    for row in rows.iter_mut() {
+        row.resize(width, String::default());
+    }
+
+    rows
+}
+
+/**
+ * 每一栏的宽度比例，未提供宽度比例的栏平分剩余宽度
+ */
+fn columns(be: &BlockElement) -> Vec<(f64, &BlockElement)> {
+    let columns: Vec<(Option<f64>, &BlockElement)> = be.child.iter()
+        .filter(|column| matches!(column.line_type, BlockType::Column))
+        .map(|column| (column.status.get("width_ratio").and_then(|v| v.as_f64()), column))
+        .collect();
+
+    let missing = columns.iter().filter(|(ratio, _)| ratio.is_none()).count();
+    let rest = 1.0 - columns.iter().filter_map(|(ratio, _)| *ratio).sum::<f64>();
+    let width = if rest > 0.0 { rest / missing.max(1) as f64 } else { 1.0 / columns.len().max(1) as f64 };
+    columns.into_iter()
+        .map(|(ratio, column)| (ratio.unwrap_or(width), column))
+        .collect()
+}
+
+/**
+ * 将fmt::Write的输出写入io::Write，fmt::Write只能返回fmt::Error，io错误暂存后由error取出
+ */
+pub struct IoWriter<'a, W: io::Write + ?Sized> {
+    out: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<'a, W: io::Write + ?Sized> IoWriter<'a, W> {
+    pub fn new(out: &'a mut W) -> Self {
+        IoWriter { out, error: None }
+    }
+
+    /**
+     * 写入时发生的io错误，没有时为渲染本身的错误
+     */
+    pub fn error(&mut self) -> io::Error {
+        self.error.take().unwrap_or_else(|| io::Error::other("render error"))
+    }
+}
+
+impl<W: io::Write + ?Sized> fmt::Write for IoWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/**
+ * 将整个页面的渲染结果写入io::Write
+ */
+pub fn write<R: Renderer + ?Sized, W: io::Write + ?Sized>(renderer: &R, list: &[BlockElement], out: &mut W) -> io::Result<()> {
+    let mut sink = IoWriter::new(out);
+    renderer.blocks(list, &mut sink).map_err(|_| sink.error())
+}
+
+/**
+ * 将连续的同类列表项分为一组，其他块各自成组
+ */
+pub fn group(list: &[BlockElement]) -> Vec<&[BlockElement]> {
+    let mut output = Vec::new();
+    let mut i = 0;
+    while i < list.len() {
+        let line_type = &list[i].line_type;
+        let len = if line_type.is_list() {
+            list[i..].iter().take_while(|be| be.line_type == *line_type).count()
+        } else {
+            1
+        };
+        output.push(&list[i..i + len]);
+        i += len;
+    }
+
+    output
+}
+
+
+/**
+ * 块树的访问者，enter返回false时跳过该块的子块
+ */
+pub trait Visitor {
+    fn enter(&mut self, be: &BlockElement, depth: usize) -> bool {
+        let _ = (be, depth);
+        true
+    }
+
+    fn leave(&mut self, be: &BlockElement, depth: usize) {
+        let _ = (be, depth);
+    }
+}
+
+/**
+ * 深度优先遍历块树
+ */
+pub fn walk<V: Visitor + ?Sized>(list: &[BlockElement], visitor: &mut V) {
+    fn walk_depth<V: Visitor + ?Sized>(list: &[BlockElement], visitor: &mut V, depth: usize) {
+        for be in list.iter() {
+            if visitor.enter(be, depth) {
+                walk_depth(&be.child, visitor, depth + 1);
+            }
+            visitor.leave(be, depth);
+        }
+    }
+
+    walk_depth(list, visitor, 0);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::block::Block;
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slug("Hello World"), "hello-world");
+        assert_eq!(slug("  What's new? (v2.0)  "), "whats-new-v20");
+        assert_eq!(slug("snake_case and-dash"), "snake_case-and-dash");
+        assert_eq!(slug("中文 标题"), "中文-标题");
+        assert_eq!(slug("!!!"), "");
+    }
+
+    #[test]
+    fn duplicate_anchors() {
+        let mut block = Block::from_markdown("# Intro\n\n## Intro\n\n### Intro");
+        block.outline();
+        let anchors: Vec<&str> = block.inner.iter().map(|be| be.anchor.as_str()).collect();
+        assert_eq!(anchors, ["intro", "intro-1", "intro-2"]);
+    }
+
+    #[test]
+    fn encode_paths() {
+        assert_eq!(encode_path("a b/C#.md"), "a%20b/C%23.md");
+        assert_eq!(encode_path("50%?&=.md"), "50%25%3F%26%3D.md");
+        assert_eq!(encode_href("https://x.com/a b?q=(1)#h"), "https://x.com/a%20b?q=%281%29#h");
+    }
+
+    #[test]
+    fn indent_lines() {
+        let mut output = String::default();
+        let mut out = Indent::new(&mut output, "> ").blank(Some(">"));
+        out.write_str("\n\none\ntwo").unwrap();
+        out.write_str("\n\nthree\n\n").unwrap();
+        assert_eq!(output, "> one\n> two\n>\n> three");
+
+        let mut output = String::default();
+        let mut out = Indent::new(&mut output, "\t").blank(None);
+        out.write_str("a\n\n\nb").unwrap();
+        assert_eq!(output, "\ta\n\tb");
+    }
+
+    #[test]
+    fn indent_lead_only_before_content() {
+        let mut output = String::from("item");
+        Indent::new(&mut output, "  ").lead("\n\n").write_str("\n\n").unwrap();
+        assert_eq!(output, "item");
+
+        Indent::new(&mut output, "  ").lead("\n").write_str("child\n\nmore").unwrap();
+        assert_eq!(output, "item\n  child\n\n  more");
+
+        let mut output = String::from("- ");
+        Indent::new(&mut output, "  ").hanging().write_str("first\nsecond").unwrap();
+        assert_eq!(output, "- first\n  second");
+    }
+
+    #[test]
+    fn stream_keeps_lists_across_pushes() {
+        let renderer = Markdown::default();
+        let mut output = String::default();
+        let mut stream = Stream::new(&renderer, &mut output);
+        stream.push(Block::from_markdown("# Title\n\n1. one").inner).unwrap();
+        stream.push(Block::from_markdown("1. two\n\nend").inner).unwrap();
+        stream.finish().unwrap();
+
+        let whole = Block::from_markdown("# Title\n\n1. one\n2. two\n\nend");
+        assert_eq!(output, Markdown::default().document(&whole.inner));
+        assert!(output.contains("1. one\n2. two"));
+    }
+
+    #[test]
+    fn stream_into_io() {
+        let block = Block::from_markdown("- a\n  - b\n\n> quote");
+        let mut bytes = Vec::new();
+        write(&Gfm::default(), &block.inner, &mut bytes).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), Gfm::default().document(&block.inner));
+    }
+
+    #[test]
+    fn default_hooks_render_gfm() {
+        struct Plain;
+        impl Renderer for Plain {
+            fn divider(&self, _be: &BlockElement) -> String {
+                "***".to_string()
+            }
+        }
+
+        let block = Block::from_markdown("# Title\n\n- a\n  - b\n\n---\n\n> quote");
+        let gfm = Gfm::default().document(&block.inner);
+        assert_eq!(Plain.document(&block.inner), gfm.replace("---", "***"));
+    }
+}
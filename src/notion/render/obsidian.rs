@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use super::{Renderer, Child, surround, icon};
+use super::gfm::blockquote;
+use super::markdown::link;
+use super::super::{block::BlockElement, id::PageId};
+
+
+/**
+ * Obsidian笔记，在GFM的基础上：
+ * 导出范围内的页面链接输出为wikilink，标注块输出为callout，折叠块输出为默认折叠的callout
+ */
+#[derive(Debug, Default, Clone)]
+pub struct Obsidian {
+    // 页面ID => 笔记在库中的路径（不含扩展名）
+    pages: HashMap<PageId, String>,
+}
+
+impl Obsidian {
+    /**
+     * 导出范围内的页面，指向这些页面的链接输出为wikilink
+     */
+    pub fn pages(mut self, pages: HashMap<PageId, String>) -> Self {
+        self.pages = pages;
+        self
+    }
+
+    /**
+     * callout的首行为类型、折叠标记和标题，text和子块在之后的行，每行加上引用标记
+     */
+    fn callout_block(fold: &str, title: &str, text: &str, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        let mut quote = blockquote(out);
+        quote.write_str(format!("[!note]{} {}", fold, title).trim_end())?;
+        match text.is_empty() {
+            true => surround(&mut quote, "\n", child, ""),
+            false => {
+                quote.write_str(&("\n".to_string() + text))?;
+                surround(&mut quote, "\n\n", child, "")
+            },
+        }
+    }
+}
+
+impl Renderer for Obsidian {
+    /**
+     * 链接文本与笔记名相同或就是链接地址（页面链接块）时省略别名
+     */
+    fn link(&self, href: &str, text: String) -> String {
+        match PageId::from_href(href).and_then(|id| self.pages.get(&id)) {
+            Some(note) if text.is_empty() || text == href || note.rsplit('/').next() == Some(text.as_str()) => format!("[[{}]]", note),
+            Some(note) => format!("[[{}|{}]]", note, text),
+            None => link(href, text),
+        }
+    }
+
+    /**
+     * callout的标题只能有一行，硬换行改为空格
+     */
+    fn toggle(&self, _be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Obsidian::callout_block("-", &text.replace("\\\n", " "), "", child, out)
+    }
+
+    /**
+     * 图标作为callout的标题
+     */
+    fn callout(&self, be: &BlockElement, text: String, child: Child, out: &mut dyn fmt::Write) -> fmt::Result {
+        Obsidian::callout_block("", &icon(be), &text, child, out)
+    }
+}
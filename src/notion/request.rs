@@ -7,6 +7,8 @@ use std::env;
 
 const REQ_TIME_S: u64 = 10;
 const REQ_TIME_NS: u32 = 0;
+// 下载文件的超时时间
+const DOWNLOAD_TIME_S: u64 = 120;
 
 
 #[allow(dead_code)]
@@ -42,7 +44,7 @@ impl Request {
         let client = match method {
             RequestMethod::GET => client.get(path),
             RequestMethod::POST => client.post(path).json(&body),
-            RequestMethod::PATCH => client.patch(path),
+            RequestMethod::PATCH => client.patch(path).json(&body),
             RequestMethod::DELETE => client.delete(path),
         };
 
@@ -61,13 +63,26 @@ impl Request {
         }
     }
 
+    /**
+     * 下载文件内容，文件链接已自带签名，不附加Notion的认证信息
+     */
+    pub fn download(url: &str) -> Result<Vec<u8>> {
+        let res = reqwest::blocking::Client::new().get(url)
+            .timeout(Duration::new(DOWNLOAD_TIME_S, REQ_TIME_NS))
+            .send()?;
+
+        let code = res.status();
+        if !code.is_success() {
+            return Err(CommErr::DownloadErr("<".to_string() + code.as_str() + ">:" + url).into());
+        }
+
+        Ok(res.bytes()?.to_vec())
+    }
+
     fn get_header(&self, method: RequestMethod) -> HeaderMap {
         let mut header = self.header.clone();
-        match method {
-            RequestMethod::POST => {
-                header.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-            },
-            _ => (),
+        if let RequestMethod::POST | RequestMethod::PATCH = method {
+            header.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         }
 
         header
@@ -0,0 +1,306 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use anyhow::Result;
+use serde_json::json;
+use strum::EnumProperty;
+use strum_macros::{Display as EnumDisplay, EnumString};
+
+use super::{NotionBuilder, Json, page::{Page, PageSchema}, block::BlockReader, asset::Assets, database::property_value, front_matter::{FrontMatter, MatterFormat}, render::{self, Renderer}};
+
+
+/**
+ * 静态网站生成器，format为其front matter的格式
+ */
+#[derive(EnumDisplay, EnumString, EnumProperty, Debug, Clone, Copy, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum Generator {
+    // content/<栏目>/<slug>/index.md，资源与文章放在同一目录
+    #[strum(props(format="toml"))]
+    Hugo,
+    // 同Hugo，另外为栏目生成_index.md
+    #[strum(props(format="toml"))]
+    Zola,
+    // _posts/<日期>-<slug>.md，资源放在assets/<slug>/
+    #[strum(props(format="yaml"))]
+    Jekyll,
+}
+
+
+/**
+ * 将数据库中的页面导出为静态网站的文章
+ * 默认的属性：标签为Tags，发布日期为Date（没有时使用创建时间），状态为Status且只有Published视为已发布
+ * 未发布的文章默认导出为草稿
+ */
+#[derive(Debug, Clone)]
+pub struct Site {
+    generator: Generator,
+    section: String,
+    tags: String,
+    date: String,
+    status: String,
+    published: Vec<String>,
+    drafts: bool,
+    schema: PageSchema,
+}
+
+impl Site {
+    pub fn new(generator: Generator) -> Self {
+        Site {
+            generator,
+            section: "posts".to_string(),
+            tags: "Tags".to_string(),
+            date: "Date".to_string(),
+            status: "Status".to_string(),
+            published: vec!["Published".to_string()],
+            drafts: true,
+            schema: PageSchema::default(),
+        }
+    }
+
+    /**
+     * Hugo和Zola中文章所在的栏目
+     */
+    pub fn section(mut self, section: &str) -> Self {
+        self.section = section.to_string();
+        self
+    }
+
+    pub fn tags(mut self, property: &str) -> Self {
+        self.tags = property.to_string();
+        self
+    }
+
+    pub fn date(mut self, property: &str) -> Self {
+        self.date = property.to_string();
+        self
+    }
+
+    /**
+     * 状态属性及视为已发布的状态值，其他状态的文章标为草稿
+     */
+    pub fn status(mut self, property: &str, published: &[&str]) -> Self {
+        self.status = property.to_string();
+        self.published = published.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /**
+     * 是否导出未发布的文章，为false时跳过未发布的文章而不是标为草稿
+     */
+    pub fn drafts(mut self, drafts: bool) -> Self {
+        self.drafts = drafts;
+        self
+    }
+
+    /**
+     * 解析页面时使用的属性映射
+     */
+    pub fn schema(mut self, schema: PageSchema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /**
+     * 导出查询到的全部页面，查询的筛选条件即发布条件
+     */
+    pub fn export<R: Renderer + ?Sized, P: AsRef<Path>>(&self, query: &NotionBuilder, dir: P, renderer: &R) -> Result<()> {
+        let dir = dir.as_ref();
+        let matter = FrontMatter::new(MatterFormat::from_str(self.generator.get_str("format").unwrap_or_default())?);
+        let mut slugs = HashSet::new();
+        for value in query.results(None)?.iter() {
+            let mut page = Page::with_schema(value, &self.schema)?;
+            if !self.drafts && self.draft(&page) {
+                continue;
+            }
+            let (date, fields) = self.fields(&page);
+            let slug = unique(&page, &mut slugs);
+            let (path, mut assets) = self.locate(dir, &slug, &date);
+
+            assets.localize(&mut page)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = BufWriter::new(File::create(path)?);
+            file.write_all(matter.format(&fields).as_bytes())?;
+            page.write_with(renderer, &mut BlockReader::default(), &mut file, |list| assets.localize_blocks(list))?;
+            file.flush()?;
+        }
+
+        // Zola的栏目需要_index.md，已存在时保留
+        let index = dir.join("content").join(&self.section).join("_index.md");
+        if self.generator == Generator::Zola && !index.exists() {
+            fs::create_dir_all(dir.join("content").join(&self.section))?;
+            fs::write(index, matter.format(&[("title".to_string(), json!(self.section)), ("sort_by".to_string(), json!("date"))]))?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * 状态不是已发布的文章为草稿，没有状态属性时视为已发布
+     */
+    fn draft(&self, page: &Page) -> bool {
+        match property(page, &self.status) {
+            Json::String(status) => !self.published.contains(&status),
+            _ => false,
+        }
+    }
+
+    /**
+     * 文章的发布日期和front matter字段
+     */
+    fn fields(&self, page: &Page) -> (String, Vec<(String, Json)>) {
+        let date = match property(page, &self.date) {
+            Json::String(date) => date,
+            Json::Object(range) => range.get("start").and_then(|s| s.as_str()).unwrap_or(&page.created_time).to_string(),
+            _ => page.created_time.to_string(),
+        };
+        let tags = match property(page, &self.tags) {
+            Json::Array(tags) => tags,
+            Json::String(tag) => vec![Json::String(tag)],
+            _ => Vec::new(),
+        };
+        let draft = self.draft(page);
+
+        let mut fields = vec![("title".to_string(), json!(page.title)), ("date".to_string(), json!(date))];
+        let rest = match self.generator {
+            Generator::Hugo => vec![("lastmod", json!(page.edited_time)), ("draft", json!(draft)), ("tags", json!(tags))],
+            Generator::Zola => vec![("updated", json!(page.edited_time)), ("draft", json!(draft)), ("taxonomies", json!({ "tags": tags }))],
+            Generator::Jekyll => vec![("last_modified_at", json!(page.edited_time)), ("tags", json!(tags)), ("published", if draft { json!(false) } else { Json::Null })],
+        };
+        fields.extend(rest.into_iter().map(|(key, value)| (key.to_string(), value)));
+
+        (date, fields)
+    }
+
+    /**
+     * 文章的文件路径和资源的保存位置
+     */
+    fn locate(&self, dir: &Path, slug: &str, date: &str) -> (PathBuf, Assets) {
+        match self.generator {
+            Generator::Hugo | Generator::Zola => {
+                let bundle = dir.join("content").join(&self.section).join(slug);
+                (bundle.join("index.md"), Assets::new(bundle, ""))
+            },
+            Generator::Jekyll => (
+                dir.join("_posts").join(format!("{}-{}.md", date.get(..10).unwrap_or(date), slug)),
+                Assets::new(dir.join("assets").join(slug), &("/assets/".to_string() + slug)),
+            ),
+        }
+    }
+}
+
+/**
+ * 页面属性值的Json形式，没有该属性时为Null
+ */
+fn property(page: &Page, name: &str) -> Json {
+    page.properties.iter()
+        .find(|p| p.property.get_val() == name)
+        .map(|p| property_value(&p.value))
+        .unwrap_or_default()
+}
+
+/**
+ * 由标题生成不重复的slug，标题中没有可用字符时使用页面ID
+ */
+fn unique(page: &Page, slugs: &mut HashSet<String>) -> String {
+    let base = match render::slug(&page.title) {
+        slug if slug.trim_matches('-').is_empty() => page.id.simple(),
+        slug => slug,
+    };
+
+    let mut slug = base.to_string();
+    let mut n = 1;
+    while !slugs.insert(slug.to_string()) {
+        n += 1;
+        slug = format!("{}-{}", base, n);
+    }
+
+    slug
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(title: &str, properties: Json) -> Page {
+        let mut properties = properties;
+        properties["Name"] = json!({ "id": "title", "type": "title", "title": [{ "type": "text", "plain_text": title }] });
+        Page::with_schema(&json!({
+            "object": "page",
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "created_time": "2023-05-01T09:30:00.000Z",
+            "last_edited_time": "2023-05-02T10:00:00.000Z",
+            "created_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "last_edited_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "archived": false,
+            "properties": properties,
+        }), &PageSchema::default()).unwrap()
+    }
+
+    fn field<'a>(fields: &'a [(String, Json)], key: &str) -> &'a Json {
+        &fields.iter().find(|(k, _)| k == key).unwrap().1
+    }
+
+    #[test]
+    fn hugo_fields() {
+        let page = page("Hello", json!({
+            "Tags": { "id": "a", "type": "multi_select", "multi_select": [{ "name": "rust" }, { "name": "notion" }] },
+            "Status": { "id": "b", "type": "status", "status": { "name": "Draft" } },
+            "Date": { "id": "c", "type": "date", "date": { "start": "2023-06-01", "end": null } },
+        }));
+        let site = Site::new(Generator::Hugo);
+        assert!(site.draft(&page));
+
+        let (date, fields) = site.fields(&page);
+        assert_eq!(date, "2023-06-01");
+        assert_eq!(field(&fields, "title"), &json!("Hello"));
+        assert_eq!(field(&fields, "draft"), &json!(true));
+        assert_eq!(field(&fields, "tags"), &json!(["rust", "notion"]));
+        assert_eq!(field(&fields, "lastmod"), &json!("2023-05-02T10:00:00.000Z"));
+    }
+
+    #[test]
+    fn jekyll_fields() {
+        let published = page("Hello", json!({ "Status": { "id": "b", "type": "status", "status": { "name": "Published" } } }));
+        let site = Site::new(Generator::Jekyll);
+        assert!(!site.draft(&published));
+
+        // 没有日期属性时使用创建时间，已发布的文章不输出published
+        let (date, fields) = site.fields(&published);
+        assert_eq!(date, "2023-05-01T09:30:00.000Z");
+        assert_eq!(field(&fields, "published"), &Json::Null);
+        assert_eq!(field(&fields, "tags"), &json!([]));
+
+        let draft = page("Hello", json!({ "State": { "id": "b", "type": "select", "select": { "name": "Ready" } } }));
+        let site = site.status("State", &["Done"]);
+        assert_eq!(field(&site.fields(&draft).1, "published"), &json!(false));
+        assert!(!site.draft(&page("Hello", json!({}))));
+    }
+
+    #[test]
+    fn locations() {
+        // Assets没有公开字段，按Debug输出比较保存目录和引用前缀
+        let dir = Path::new("site");
+        let bundle = dir.join("content").join("blog").join("hello");
+        let (path, assets) = Site::new(Generator::Hugo).section("blog").locate(dir, "hello", "2023-06-01");
+        assert_eq!(path, bundle.join("index.md"));
+        assert_eq!(format!("{:?}", assets), format!("{:?}", Assets::new(bundle, "")));
+
+        let (path, assets) = Site::new(Generator::Jekyll).locate(dir, "hello", "2023-06-01T08:00:00.000Z");
+        assert_eq!(path, dir.join("_posts").join("2023-06-01-hello.md"));
+        assert_eq!(format!("{:?}", assets), format!("{:?}", Assets::new(dir.join("assets").join("hello"), "/assets/hello")));
+    }
+
+    #[test]
+    fn unique_slugs() {
+        let mut slugs = HashSet::new();
+        assert_eq!(unique(&page("Hello World", json!({})), &mut slugs), "hello-world");
+        assert_eq!(unique(&page("hello world", json!({})), &mut slugs), "hello-world-2");
+        assert_eq!(unique(&page("!!!", json!({})), &mut slugs), "598337872cf94fdf8782e53db20768a5");
+    }
+}
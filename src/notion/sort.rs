@@ -10,6 +10,7 @@ pub enum Direction {
     Ascending,
 }
 
+#[derive(Default)]
 pub struct Sort {
     map: HashMap<PropertyType, Direction>
 }
@@ -25,12 +26,6 @@ impl Sort {
     }
 }
 
-impl Default for Sort {
-    fn default() -> Self {
-        Sort { map: HashMap::new() }
-    }
-}
-
 impl FmtDisplay for Sort {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut output = String::from("");
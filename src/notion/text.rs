@@ -1,76 +1,116 @@
 use std::str::FromStr;
 use strum::EnumProperty;
 use strum_macros::{Display as Enumdisplay, EnumString};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
 
+use super::id::{PageId, DatabaseId, UserId};
 
-#[derive(Enumdisplay, EnumString, EnumProperty, Debug)]
+
+#[derive(Enumdisplay, EnumString, EnumProperty, Debug, Clone, PartialEq)]
 #[strum(serialize_all = "snake_case")] 
 pub enum BlockType {
     // rich text
-    #[strum(props(md="{}"))]
+    #[strum(props(md="{}", gfm="{}", html="<p>{}</p>"))]
     Paragraph,
     #[strum(serialize="heading_1")]
-    #[strum(props(md="# {}"))]
+    #[strum(props(md="# {}", gfm="# {}", html="<h1{status}>{}</h1>", status="is_toggleable"))]
     Heading1,
     #[strum(serialize="heading_2")]
-    #[strum(props(md="## {}"))]
+    #[strum(props(md="## {}", gfm="## {}", html="<h2{status}>{}</h2>", status="is_toggleable"))]
     Heading2,
     #[strum(serialize="heading_3")]
-    #[strum(props(md="### {}", cmd="false"))]
+    #[strum(props(md="### {}", gfm="### {}", html="<h3{status}>{}</h3>", cmd="false", status="is_toggleable"))]
     Heading3,
-    #[strum(props(md="* {}"))]
+    #[strum(props(md="* {}", gfm="- {}", html="<li>{}{child}</li>"))]
     BulletedListItem,
-    #[strum(props(md="1. {}"))]
+    #[strum(props(md="{index}. {}", gfm="{index}. {}", html="<li>{}{child}</li>"))]
     NumberedListItem,
-    #[strum(props(md="- [{status}] {}", cmd="false"))]
+    #[strum(props(md="- [{status}] {}", gfm="- [{status}] {}", html="<li><input type=\"checkbox\" disabled{status}/><span>{}</span>{child}</li>", cmd="false", status="checked"))]
     ToDo,
-    #[strum(props(md="<details><summary>{}</summary>{child}</details>", cmd="false"))]
+    #[strum(props(md="<details><summary>{}</summary>{child}</details>", gfm="- {}", html="<details><summary>{}</summary>{child}</details>", cmd="false"))]
     Toggle,
-    #[strum(props(md="<aside>{status}{}</aside>", cmd="false"))]
+    #[strum(props(md="<aside>{status}{}</aside>", gfm="{status} {}", html="<aside><span class=\"callout-icon\">{status}</span><div>{}{child}</div></aside>", cmd="false", status="icon"))]
     Callout,
-    #[strum(props(md="> {}"))]
+    #[strum(props(md="> {}", gfm="{}", html="<blockquote>{}{child}</blockquote>"))]
     Quote,
-    #[strum(props(md="```{status}\n{}\n```"))]
+    #[strum(props(md="```{status}\n{}\n```", gfm="```{status}\n{}\n```", html="<pre><code class=\"language-{status}\">{}</code></pre>", status="language"))]
     Code,
     // special
-    #[strum(props(md="---"))]
+    #[strum(props(md="---", gfm="---", html="<hr/>"))]
     Divider,
-    #[strum(props(md="$${}$$"))]
+    #[strum(props(md="$${}$$", gfm="$$\n{}\n$$", html="<div class=\"equation\">$${}$$</div>"))]
     Equation,
     Template,
+    #[strum(props(md="{}", gfm="{}", html="<p class=\"page-link\">{}</p>"))]
     ChildPage,
+    #[strum(props(md="{}", gfm="{}", html="<p class=\"page-link\">{}</p>"))]
     ChildDatabase,
+    #[strum(props(md="[{}]({url})", gfm="[{}]({url})"))]
     Embed,
+    // media
+    #[strum(props(md="![{}]({url})", gfm="![{}]({url})", html="<img src=\"{url}\" alt=\"{}\"/>"))]
     Image,
+    #[strum(props(md="[{}]({url})", gfm="[{}]({url})", html="<video controls src=\"{url}\"></video>"))]
     Video,
+    #[strum(props(md="[{}]({url})", gfm="[{}]({url})", html="<a class=\"file\" href=\"{url}\">{}</a>"))]
     File,
+    #[strum(props(md="[{}]({url})", gfm="[{}]({url})", html="<a class=\"file\" href=\"{url}\">{}</a>"))]
     Pdf,
+    #[strum(props(md="[{}]({url})", gfm="[{}]({url})", html="<audio controls src=\"{url}\"></audio>"))]
+    Audio,
+    #[strum(props(md="[{}]({url})", gfm="[{}]({url})"))]
     Bookmark,
     TableOfContents,
     Column,
     ColumnList,
+    #[strum(props(md="[{}]({url})", gfm="[{}]({url})"))]
     LinkPreview,
     SyncedBlock,
+    #[strum(props(md="{}", gfm="{}", html="<p class=\"page-link\">{}</p>"))]
     LinkToPage,
     Table,
     TableRow,
     Unsupported,
 }
 
-#[derive(Enumdisplay, EnumString, EnumProperty, Debug)]
+impl BlockType {
+    pub fn is_list(&self) -> bool {
+        matches!(self, BlockType::BulletedListItem | BlockType::NumberedListItem | BlockType::ToDo)
+    }
+
+    /**
+     * 块的文本所在的字段，媒体和书签的文本为说明文字
+     */
+    pub fn text_key(&self) -> Option<&'static str> {
+        {
+            use BlockType::*;
+            match self {
+                Divider | Equation | Table | TableRow | LinkPreview | ColumnList | Column | SyncedBlock | ChildPage | ChildDatabase | LinkToPage | TableOfContents | Unsupported => None,
+                Image | Video | File | Pdf | Audio | Bookmark | Embed => Some("caption"),
+                _ => Some("rich_text"),
+            }
+        }
+    }
+
+    pub fn is_media(&self) -> bool {
+        matches!(self, BlockType::Image | BlockType::Video | BlockType::File | BlockType::Pdf | BlockType::Audio)
+    }
+}
+
+#[derive(Enumdisplay, EnumString, EnumProperty, Debug, Clone, PartialEq)]
 #[strum(serialize_all = "snake_case")] 
 pub enum Annotation {
-    #[strum(props(md="**{}**", mdrpl="__{}__"))]
+    #[strum(props(md="**{}**", mdrpl="__{}__", gfm="**{}**", html="<strong>{}</strong>"))]
     Bold,
-    #[strum(props(md="*{}*", mdrpl="_{}_"))]
+    #[strum(props(md="*{}*", mdrpl="_{}_", gfm="*{}*", html="<em>{}</em>"))]
     Italic,
-    #[strum(props(md="<del>{}</del>", cmd="false"))]
+    #[strum(props(md="<del>{}</del>", gfm="~~{}~~", html="<del>{}</del>", cmd="false"))]
     Strikethrough,
-    #[strum(props(md="<u>{}</u>", cmd="false"))]
+    #[strum(props(md="<u>{}</u>", gfm="{}", html="<u>{}</u>", cmd="false"))]
     Underline,
-    #[strum(props(md="`{}`"))]
+    #[strum(props(md="`{}`", gfm="`{}`", html="<code>{}</code>"))]
     Code,
-    #[strum(props(md="<font {color}>{}</font>"))]
+    #[strum(props(md="<font {color}>{}</font>", html="<span class=\"highlight-{color}\">{}</span>"))]
     Color(AnnoColor),
 }
 
@@ -99,13 +139,54 @@ impl Annotation {
         }
     }
 
-    pub fn sort(list:& mut Vec<Annotation>) {
-        list.sort_by(|x, y| x.get_serial_num().cmp(&y.get_serial_num()));
+    pub fn sort(list: &mut [Annotation]) {
+        list.sort_by_key(|x| x.get_serial_num());
     }
 }
 
 
-#[derive(Enumdisplay, EnumString, EnumProperty, Default, Debug)]
+/**
+ * rich text片段的类型，普通文本之外的提及和行内公式
+ */
+#[derive(Enumdisplay, EnumProperty, Default, Debug, Clone, PartialEq)]
+#[strum(serialize_all = "snake_case")] 
+pub enum Inline {
+    #[default] Text,
+    #[strum(props(md="@{}", gfm="@{}", html="<span class=\"mention\">@{}</span>"))]
+    User { id: UserId, name: String },
+    Page(PageId),
+    Database(DatabaseId),
+    #[strum(props(html="<time>{}</time>"))]
+    Date { start: String, end: Option<String> },
+    LinkPreview(String),
+    TemplateMention(String),
+    #[strum(props(md="${}$", gfm="${}$", html="<span class=\"inline-equation\">${}$</span>"))]
+    Equation(String),
+}
+
+impl Inline {
+    /**
+     * 用户提及输出用户名，日期提及输出格式化后的日期，其他类型使用原文本
+     */
+    pub fn text(&self) -> Option<String> {
+        // 2023-05-01T09:30:00.000+08:00 => 2023-05-01 09:30
+        let date = |value: &str| match value.split_once('T') {
+            Some((day, time)) => day.to_string() + " " + time.get(..5).unwrap_or(time),
+            None => value.to_string(),
+        };
+
+        match self {
+            Inline::User { name, .. } => Some(name.to_string()),
+            Inline::Date { start, end: Some(end) } => Some(date(start) + " → " + &date(end)),
+            Inline::Date { start, end: None } => Some(date(start)),
+            Inline::Equation(expression) => Some(expression.to_string()),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Enumdisplay, EnumString, EnumProperty, Default, Debug, Clone, PartialEq)]
 #[strum(serialize_all = "snake_case")] 
 pub enum AnnoColor {
     #[default] Default,
@@ -145,5 +226,33 @@ pub enum AnnoColor {
     Yellow,
     #[strum(props(md="style=background:yellow"))]
     YellowBackground,
+    // 无法识别的新增颜色，保留原始名称，渲染时按默认颜色处理
+    #[strum(default)]
+    Other(String),
+}
+
+impl AnnoColor {
+    /**
+     * 颜色在Notion API中的名称，无法识别的颜色原样返回
+     */
+    pub fn name(&self) -> String {
+        match self {
+            AnnoColor::Other(name) => name.to_string(),
+            color => color.to_string(),
+        }
+    }
+}
+
+impl Serialize for AnnoColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for AnnoColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(AnnoColor::from_str(&name).unwrap_or_default())
+    }
 }
 
@@ -0,0 +1,253 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf, Component};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use anyhow::Result;
+
+use super::{Notion, id::PageId, block::{Block, BlockElement, BlockReader}, page::page_title, render::{Renderer, Visitor, encode_path}, text::BlockType};
+
+
+// 文件名的最大字符数
+const NAME_LIMIT: usize = 100;
+
+
+/**
+ * 以某个页面为根的页面树，子页面导出到以父页面命名的子目录中
+ * 查找子页面和页面链接需要页面的全部内容，因此内容在获取时缓存，不使用Page::write_with流式写出
+ */
+#[derive(Debug)]
+pub struct PageTree {
+    pub id: PageId,
+    pub title: String,
+    // 相对导出目录的文件路径
+    pub path: PathBuf,
+    pub content: Block,
+    pub children: Vec<PageTree>,
+}
+
+impl PageTree {
+    /**
+     * 获取页面及其全部子页面，页面链接指向的页面若不在树中则作为链接所在页面的子页面
+     * id可以是页面ID或页面的分享链接
+     */
+    pub fn fetch(id: &str) -> Result<Self> {
+        PageTree::fetch_with(id, &mut BlockReader::default())
+    }
+
+    /**
+     * 以指定的解析模式获取页面树
+     */
+    pub fn fetch_with(id: &str, reader: &mut BlockReader) -> Result<Self> {
+        let id: PageId = id.parse()?;
+        let title = page_title(&Notion::Pages(id.clone()).retrieve()?);
+        let mut visited = HashSet::new();
+        let mut tree = PageTree::fetch_page(id, &title, reader, &mut visited)?;
+        tree.follow_links(reader, &mut visited)?;
+
+        Ok(tree)
+    }
+
+    fn fetch_page(id: PageId, title: &str, reader: &mut BlockReader, visited: &mut HashSet<PageId>) -> Result<Self> {
+        visited.insert(id.clone());
+        let mut content = reader.read(id.clone().into())?;
+        content.outline();
+
+        let mut children = Vec::new();
+        for (target, title) in targets(&content, BlockType::ChildPage) {
+            if !visited.contains(&target) {
+                children.push(PageTree::fetch_page(target, &title, reader, visited)?);
+            }
+        }
+
+        Ok(PageTree { id, title: title.to_string(), path: PathBuf::new(), content, children })
+    }
+
+    fn follow_links(&mut self, reader: &mut BlockReader, visited: &mut HashSet<PageId>) -> Result<()> {
+        for (target, _) in targets(&self.content, BlockType::LinkToPage) {
+            if !visited.contains(&target) {
+                let title = page_title(&Notion::Pages(target.clone()).retrieve()?);
+                self.children.push(PageTree::fetch_page(target, &title, reader, visited)?);
+            }
+        }
+
+        for child in self.children.iter_mut() {
+            child.follow_links(reader, visited)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * 将整个页面树写入目录，页面之间的链接改写为相对路径
+     */
+    pub fn export<R: Renderer + ?Sized, P: AsRef<Path>>(&mut self, dir: P, renderer: &R, extension: &str) -> Result<()> {
+        self.locate(Path::new(""), extension, &mut HashSet::new());
+
+        let mut pages = HashMap::new();
+        self.collect(&mut pages);
+        self.write(dir.as_ref(), renderer, &pages)
+    }
+
+    /**
+     * 分配文件路径，同一目录下的文件名不区分大小写地去重
+     */
+    fn locate(&mut self, dir: &Path, extension: &str, names: &mut HashSet<String>) {
+        let stem = unique_name(file_name(&self.title), names);
+        self.path = dir.join(stem.to_string() + "." + extension);
+        let dir = dir.join(stem);
+        let mut names = HashSet::new();
+        for child in self.children.iter_mut() {
+            child.locate(&dir, extension, &mut names);
+        }
+    }
+
+    fn collect(&self, pages: &mut HashMap<PageId, (PathBuf, String)>) {
+        pages.insert(self.id.clone(), (self.path.to_path_buf(), self.title.to_string()));
+        for child in self.children.iter() {
+            child.collect(pages);
+        }
+    }
+
+    fn write<R: Renderer + ?Sized>(&mut self, dir: &Path, renderer: &R, pages: &HashMap<PageId, (PathBuf, String)>) -> Result<()> {
+        let base = self.path.parent().unwrap_or(Path::new(""));
+        let links: HashMap<PageId, String> = pages.iter()
+            .map(|(id, (path, _))| (id.clone(), relative(base, path)))
+            .collect();
+        retitle(&mut self.content.inner, pages);
+        self.content.relink(&links);
+
+        let path = dir.join(&self.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        self.content.write(renderer, &mut file)?;
+        file.flush()?;
+
+        for child in self.children.iter_mut() {
+            child.write(dir, renderer, pages)?;
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * 页面内容中某种页面块（可能嵌套在其他块中）指向的页面ID和链接文本
+ */
+fn targets(content: &Block, line_type: BlockType) -> Vec<(PageId, String)> {
+    struct Targets(BlockType, Vec<(PageId, String)>);
+    impl Visitor for Targets {
+        fn enter(&mut self, be: &BlockElement, _depth: usize) -> bool {
+            if let (true, Some(target)) = (be.line_type == self.0, be.page_target()) {
+                self.1.push((target, be.line.iter().map(|t| t.text.as_str()).collect()));
+            }
+            true
+        }
+    }
+
+    let mut targets = Targets(line_type, Vec::new());
+    content.walk(&mut targets);
+    targets.1
+}
+
+/**
+ * 页面链接块只有链接地址，以树中页面的标题作为链接文本
+ */
+pub(crate) fn retitle(list: &mut [BlockElement], pages: &HashMap<PageId, (PathBuf, String)>) {
+    for be in list.iter_mut() {
+        if let (BlockType::LinkToPage, Some((_, title))) = (&be.line_type, be.page_target().and_then(|id| pages.get(&id))) {
+            let title = title.to_string();
+            if let Some(text) = be.line.first_mut() {
+                if text.text == text.href {
+                    text.text = title;
+                }
+            }
+        }
+
+        retitle(&mut be.child, pages);
+    }
+}
+
+/**
+ * 同一目录下的文件名不区分大小写地去重，重复时加上序号
+ */
+pub(crate) fn unique_name(name: String, names: &mut HashSet<String>) -> String {
+    let mut stem = name.to_string();
+    let mut n = 1;
+    while !names.insert(stem.to_lowercase()) {
+        n += 1;
+        stem = format!("{} ({})", name, n);
+    }
+
+    stem
+}
+
+/**
+ * 由页面标题生成合法的文件名
+ */
+pub(crate) fn file_name(title: &str) -> String {
+    let name: String = title.chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    // 先截断再去除首尾的点和空格，截断处的点同样不能出现在结尾
+    let name: String = name.trim().chars().take(NAME_LIMIT).collect();
+    let name = name.trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    // Windows的保留设备名
+    const RESERVED: [&str; 22] = [
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+        "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+    match name.trim_end() {
+        "" => "Untitled".to_string(),
+        name if RESERVED.contains(&name.to_uppercase().as_str()) => "_".to_string() + name,
+        name => name.to_string(),
+    }
+}
+
+/**
+ * 从目录base到文件path的相对链接
+ */
+fn relative(base: &Path, path: &Path) -> String {
+    let base: Vec<Component> = base.components().collect();
+    let path: Vec<Component> = path.components().collect();
+    let common = base.iter().zip(path.iter()).take_while(|(a, b)| a == b).count();
+
+    let parts: Vec<String> = std::iter::repeat_n("..".to_string(), base.len() - common)
+        .chain(path[common..].iter().map(|c| c.as_os_str().to_string_lossy().to_string()))
+        .collect();
+    encode_path(&parts.join("/"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name("a/b: c?"), "a_b_ c_");
+        assert_eq!(file_name("  .hidden. "), "hidden");
+        assert_eq!(file_name(""), "Untitled");
+        assert_eq!(file_name("con"), "_con");
+        assert_eq!(file_name(&"x".repeat(150)).len(), NAME_LIMIT);
+        assert_eq!(file_name(&("x".repeat(NAME_LIMIT - 2) + " ..tail")), "x".repeat(NAME_LIMIT - 2));
+        assert_eq!(file_name("end. ."), "end");
+    }
+
+    #[test]
+    fn unique_names() {
+        let mut names = HashSet::new();
+        assert_eq!(unique_name("Note".to_string(), &mut names), "Note");
+        assert_eq!(unique_name("note".to_string(), &mut names), "note (2)");
+        assert_eq!(unique_name("NOTE".to_string(), &mut names), "NOTE (3)");
+    }
+
+    #[test]
+    fn relative_links_encode_reserved() {
+        assert_eq!(relative(Path::new("a"), Path::new("a/C# notes.md")), "C%23%20notes.md");
+        assert_eq!(relative(Path::new("a/b"), Path::new("c/100% (draft)?.md")), "../../c/100%25%20%28draft%29%3F.md");
+        assert_eq!(relative(Path::new(""), Path::new("笔记/页面.md")), "笔记/页面.md");
+    }
+}
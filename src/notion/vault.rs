@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde_json::json;
+
+use super::{NotionBuilder, Json, id::PageId, page::{Page, PageSchema}, block::BlockReader, asset::Assets, database::property_value,
+    front_matter::{FrontMatter, MatterFormat}, render::Obsidian, tree::{PageTree, file_name, unique_name, retitle}};
+
+
+/**
+ * 将数据库或页面树导出为Obsidian库
+ * 笔记以页面标题命名，页面之间的链接和关联属性输出为wikilink，多选属性的值作为标签
+ */
+#[derive(Debug, Clone)]
+pub struct Vault {
+    attachments: String,
+    schema: PageSchema,
+}
+
+impl Vault {
+    /**
+     * attachments为附件目录在库中的路径
+     */
+    pub fn new(attachments: &str) -> Self {
+        Vault { attachments: attachments.trim_matches('/').to_string(), schema: PageSchema::default() }
+    }
+
+    /**
+     * 解析数据库页面时使用的属性映射
+     */
+    pub fn schema(mut self, schema: PageSchema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /**
+     * 数据库中的每个页面导出为库根目录下的一篇笔记
+     */
+    pub fn export_database<P: AsRef<Path>>(&self, query: &NotionBuilder, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        let pages = query.results(None)?.iter().map(|value| Page::with_schema(value, &self.schema)).collect::<Result<Vec<Page>>>()?;
+        let mut names = HashSet::new();
+        let notes: Vec<String> = pages.iter().map(|page| unique(&page.title, &mut names)).collect();
+        let targets: HashMap<PageId, String> = pages.iter().zip(notes.iter())
+            .map(|(page, note)| (page.id.clone(), note.to_string()))
+            .collect();
+
+        let renderer = Obsidian::default().pages(targets.clone());
+        let mut assets = self.assets(dir);
+        for (mut page, note) in pages.into_iter().zip(notes) {
+            let fields = fields(&page, &note, &targets);
+            assets.localize(&mut page)?;
+            write(&dir.join(note + ".md"), &fields, |file| {
+                page.write_with(&renderer, &mut BlockReader::default(), file, |list| assets.localize_blocks(list))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * 页面树按层级导出，子页面的笔记放在以父页面命名的子目录中
+     */
+    pub fn export_tree<P: AsRef<Path>>(&self, tree: &mut PageTree, dir: P) -> Result<()> {
+        let mut pages = HashMap::new();
+        locate(tree, Path::new(""), &mut HashSet::new(), &mut pages);
+        let targets: HashMap<PageId, String> = pages.iter()
+            .map(|(id, (path, _))| (id.clone(), path.with_extension("").to_string_lossy().replace('\\', "/")))
+            .collect();
+
+        let renderer = Obsidian::default().pages(targets);
+        let mut assets = self.assets(dir.as_ref());
+        write_tree(tree, dir.as_ref(), &renderer, &mut assets, &pages)
+    }
+
+    /**
+     * Obsidian按库中的路径解析附件链接
+     */
+    fn assets(&self, dir: &Path) -> Assets {
+        Assets::new(dir.join(&self.attachments), &self.attachments)
+    }
+}
+
+/**
+ * 笔记名中不能出现wikilink的保留字符，同一目录下不区分大小写地去重
+ */
+fn unique(title: &str, names: &mut HashSet<String>) -> String {
+    unique_name(file_name(title).chars().map(|c| if "#^[]".contains(c) { '_' } else { c }).collect(), names)
+}
+
+fn locate(tree: &mut PageTree, dir: &Path, names: &mut HashSet<String>, pages: &mut HashMap<PageId, (PathBuf, String)>) {
+    let name = unique(&tree.title, names);
+    tree.path = dir.join(name.to_string() + ".md");
+    pages.insert(tree.id.clone(), (tree.path.to_path_buf(), tree.title.to_string()));
+
+    let dir = dir.join(name);
+    let mut names = HashSet::new();
+    for child in tree.children.iter_mut() {
+        locate(child, &dir, &mut names, pages);
+    }
+}
+
+fn write_tree(tree: &mut PageTree, dir: &Path, renderer: &Obsidian, assets: &mut Assets, pages: &HashMap<PageId, (PathBuf, String)>) -> Result<()> {
+    let note = tree.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let fields = identity(&tree.id, &tree.title, &note);
+    retitle(&mut tree.content.inner, pages);
+    assets.localize_blocks(&mut tree.content.inner)?;
+    write(&dir.join(&tree.path), &fields, |file| Ok(tree.content.write(renderer, file)?))?;
+
+    for child in tree.children.iter_mut() {
+        write_tree(child, dir, renderer, assets, pages)?;
+    }
+
+    Ok(())
+}
+
+/**
+ * 文件名与标题不同时以标题作为别名，使链接补全仍能按标题找到笔记
+ */
+fn identity(id: &str, title: &str, note: &str) -> Vec<(String, Json)> {
+    let aliases = if title.is_empty() || title == note { Json::Null } else { json!([title]) };
+    vec![("id".to_string(), json!(id)), ("aliases".to_string(), aliases)]
+}
+
+// Obsidian自身使用的键，同名的属性改为key_2
+const RESERVED: [&str; 6] = ["id", "aliases", "created", "updated", "author", "tags"];
+
+/**
+ * Obsidian识别的front matter：时间去掉毫秒和时区，多选值汇总为tags，关联属性输出为wikilink，日期范围只保留开始日期
+ */
+fn fields(page: &Page, note: &str, targets: &HashMap<PageId, String>) -> Vec<(String, Json)> {
+    let time = |time: &str| json!(time.get(..19).unwrap_or(time));
+    let mut fields = identity(&page.id, &page.title, note);
+    fields.push(("created".to_string(), time(&page.created_time)));
+    fields.push(("updated".to_string(), time(&page.edited_time)));
+    if !page.author.name().is_empty() {
+        fields.push(("author".to_string(), json!(page.author.name())));
+    }
+
+    let mut tags: Vec<Json> = Vec::new();
+    for property in page.properties.iter() {
+        let kind = property.value.get("type").and_then(|t| t.as_str()).unwrap_or_default();
+        let value = match (kind, property_value(&property.value)) {
+            ("multi_select", Json::Array(values)) => {
+                for tag in values.iter().filter_map(|v| v.as_str()) {
+                    let tag = json!(tag.trim_start_matches('#').split_whitespace().collect::<Vec<&str>>().join("-"));
+                    if tag != json!("") && !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+                continue;
+            },
+            ("relation", Json::Array(ids)) => Json::Array(ids.iter().filter_map(|id| id.as_str()?.parse::<PageId>().ok()).map(|id| match targets.get(&id) {
+                Some(note) => json!(format!("[[{}]]", note)),
+                None => json!(id.url()),
+            }).collect()),
+            (_, Json::Object(range)) => range.get("start").cloned().unwrap_or_default(),
+            (_, value) => value,
+        };
+        let name = property.property.get_val();
+        let name = if RESERVED.contains(&name.as_str()) { name + "_2" } else { name };
+        fields.push((name, value));
+    }
+    if !tags.is_empty() {
+        fields.push(("tags".to_string(), Json::Array(tags)));
+    }
+
+    fields
+}
+
+/**
+ * 写出front matter，笔记内容由content写入
+ */
+fn write(path: &Path, fields: &[(String, Json)], content: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(FrontMatter::new(MatterFormat::Yaml).format(fields).as_bytes())?;
+    content(&mut file)?;
+    file.flush()?;
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{NewImp, front_matter::{FrontMatter, MatterFormat}};
+
+    #[test]
+    fn reserved_keys() {
+        let page = Page::new(&json!({
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "created_time": "2023-05-01T09:30:00.000Z",
+            "last_edited_time": "2023-05-02T10:00:00.000Z",
+            "created_by": { "object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4" },
+            "archived": false,
+            "properties": {
+                "Name": { "type": "title", "title": [{ "plain_text": "Kale" }] },
+                "tags": { "type": "rich_text", "rich_text": [{ "plain_text": "not a tag" }] },
+                "aliases": { "type": "rich_text", "rich_text": [{ "plain_text": "alias" }] },
+                "Topics": { "type": "multi_select", "multi_select": [{ "name": "food" }, { "name": "#green leaf" }] }
+            }
+        })).unwrap();
+
+        let fields = fields(&page, "Kale", &HashMap::new());
+        let keys: Vec<&str> = fields.iter().filter(|(_, value)| !value.is_null()).map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["id", "created", "updated", "tags_2", "aliases_2", "tags"]);
+        assert_eq!(fields.last().unwrap().1, json!(["food", "green-leaf"]));
+        assert!(FrontMatter::new(MatterFormat::Yaml).format(&fields).contains("\ncreated: \"2023-05-01T09:30:00\"\n"));
+    }
+}